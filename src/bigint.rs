@@ -0,0 +1,265 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Signed arbitrary-precision integer, used once integer arithmetic
+/// overflows `i64`.
+///
+/// The magnitude is stored as little-endian base 2^32 digits without
+/// trailing zeros, so every value has exactly one representation and zero
+/// is an empty magnitude that is never negative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    /// Parses an optionally signed string of decimal digits.
+    pub fn parse(digits: &str) -> Option<BigInt> {
+        let (negative, digits) = match digits.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, digits),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        for digit in digits.bytes() {
+            mul_small(&mut magnitude, 10);
+            add_small(&mut magnitude, u32::from(digit - b'0'));
+        }
+        Some(BigInt::new(negative, magnitude))
+    }
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let magnitude = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &digit| (acc << 32) | u64::from(digit));
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+    pub fn to_f64(&self) -> f64 {
+        // Going through the decimal form rounds correctly to the nearest f64
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> BigInt {
+        let magnitude = n.unsigned_abs();
+        BigInt::new(n < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+fn add_small(magnitude: &mut Vec<u32>, n: u32) {
+    let mut carry = u64::from(n);
+    for digit in magnitude.iter_mut() {
+        if carry == 0 {
+            return;
+        }
+        let sum = u64::from(*digit) + carry;
+        *digit = sum as u32;
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+fn mul_small(magnitude: &mut Vec<u32>, n: u32) {
+    let mut carry = 0u64;
+    for digit in magnitude.iter_mut() {
+        let product = u64::from(*digit) * u64::from(n) + carry;
+        *digit = product as u32;
+        carry = product >> 32;
+    }
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// Divides in place and returns the remainder.
+fn div_small(magnitude: &mut [u32], n: u32) -> u32 {
+    let mut remainder = 0u64;
+    for digit in magnitude.iter_mut().rev() {
+        let current = (remainder << 32) | u64::from(*digit);
+        *digit = (current / u64::from(n)) as u32;
+        remainder = current % u64::from(n);
+    }
+    remainder as u32
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = u64::from(*a.get(i).unwrap_or(&0)) + u64::from(*b.get(i).unwrap_or(&0)) + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+    result
+}
+
+/// Subtracts `b` from `a`, which must not be smaller.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &digit) in a.iter().enumerate() {
+        let mut difference = i64::from(digit) - i64::from(*b.get(i).unwrap_or(&0)) - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 1 << 32;
+            borrow = 1;
+        }
+        result.push(difference as u32);
+    }
+    result
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            );
+        }
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => BigInt::new(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut result = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, &a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.magnitude.iter().enumerate() {
+                let current = u64::from(result[i + j]) + u64::from(a) * u64::from(b) + carry;
+                result[i + j] = current as u32;
+                carry = current >> 32;
+            }
+            result[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, result)
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.magnitude.is_empty() {
+            return write!(f, "0");
+        }
+        // Peel off nine decimal digits at a time, least significant first
+        let mut magnitude = self.magnitude.clone();
+        let mut chunks = Vec::new();
+        while !magnitude.is_empty() {
+            chunks.push(div_small(&mut magnitude, 1_000_000_000));
+            while magnitude.last() == Some(&0) {
+                magnitude.pop();
+            }
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap_or(&0))?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_bigint_arithmetic() {
+    let big = BigInt::parse("123456789012345678901234567890").unwrap();
+    assert_eq!(big.to_string(), "123456789012345678901234567890");
+    assert_eq!(
+        (&big * &big).to_string(),
+        "15241578753238836750495351562536198787501905199875019052100"
+    );
+    let max = BigInt::from(i64::MAX);
+    let one = BigInt::from(1);
+    assert_eq!((&max + &one).to_string(), "9223372036854775808");
+    assert_eq!((&max + &one).to_i64(), None);
+    assert_eq!((&(&max + &one) - &one).to_i64(), Some(i64::MAX));
+    assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+    assert_eq!((&one - &big).to_string(), "-123456789012345678901234567889");
+    assert!(-&big < one && one < big);
+    assert_eq!(BigInt::parse("-0"), Some(BigInt::from(0)));
+    assert_eq!(BigInt::parse("12a"), None);
+    assert_eq!(
+        BigInt::parse("9007199254740993").unwrap().to_f64(),
+        9007199254740992.0
+    );
+}
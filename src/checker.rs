@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parser::{Expression, Operator, Statement, Type};
+#[cfg(test)]
+use crate::{parser::Parser, tokenizer::Tokenizer};
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "Number"),
+            Type::String => write!(f, "String"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Nil => write!(f, "Nil"),
+            Type::Any => write!(f, "Any"),
+        }
+    }
+}
+
+/// Infers types over a parsed program and returns every mismatch found.
+///
+/// Only annotated bindings and literals have a known type; everything else is
+/// `Any` and stays dynamically typed. The rules mirror the `RuntimeValue`
+/// operations, so a reported error is one the interpreter would raise.
+pub fn check(program: &Statement) -> Vec<String> {
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        errors: Vec::new(),
+        line: 0,
+    };
+    checker.statement(program);
+    checker.errors
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Type>>,
+    errors: Vec<String>,
+    line: u32,
+}
+
+impl Checker {
+    fn error(&mut self, message: String) {
+        self.errors
+            .push(format!("[line {}] Type error: {}", self.line, message));
+    }
+    fn declare(&mut self, name: &str, ty: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), ty);
+        }
+    }
+    fn lookup(&self, name: &str) -> Type {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .unwrap_or(Type::Any)
+    }
+    fn block(&mut self, statements: &[Statement], binding: Option<&String>) {
+        self.scopes.push(HashMap::new());
+        if let Some(name) = binding {
+            self.declare(name, Type::Any);
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Print(expr, line)
+            | Statement::ExprStmt(expr, line)
+            | Statement::Throw(expr, line) => {
+                self.line = *line;
+                self.expression(expr);
+            }
+            Statement::Program(statements) => {
+                for statement in statements {
+                    self.statement(statement);
+                }
+            }
+            Statement::Declaration(name, annotation, value, line)
+            | Statement::Const(name, annotation, value, line) => {
+                self.line = *line;
+                let actual = self.expression(value);
+                if let Some(expected) = annotation {
+                    if !compatible(*expected, actual) {
+                        self.error(format!(
+                            "Cannot initialize '{name}' of type {expected} with {actual}."
+                        ));
+                    }
+                }
+                self.declare(name, annotation.unwrap_or(Type::Any));
+            }
+            Statement::Match(subject, arms, line) => {
+                self.line = *line;
+                self.expression(subject);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    for name in arm.names() {
+                        self.declare(name, Type::Any);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.line = *line;
+                        self.expression(guard);
+                    }
+                    self.statement(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            Statement::Block(statements) => self.block(statements, None),
+            Statement::Try(body, catch, finally) => {
+                self.block(body, None);
+                if let Some((name, handler)) = catch {
+                    self.block(handler, Some(name));
+                }
+                if let Some(finally) = finally {
+                    self.block(finally, None);
+                }
+            }
+            Statement::Import(_, name, _)
+            | Statement::Struct(name, _)
+            | Statement::Enum(name, _) => self.declare(name, Type::Any),
+            Statement::DestructureDeclaration(pattern, value, line) => {
+                self.line = *line;
+                self.expression(value);
+                for name in pattern.names() {
+                    self.declare(name, Type::Any);
+                }
+            }
+            Statement::DestructureAssign(_, value, line) => {
+                self.line = *line;
+                self.expression(value);
+            }
+            Statement::ForIn(name, iterable, body, line) => {
+                self.line = *line;
+                let ty = self.expression(iterable);
+                if matches!(ty, Type::Number | Type::Bool | Type::Nil) {
+                    self.error(format!("Value of type {ty} is not iterable."));
+                }
+                self.scopes.push(HashMap::from([(name.clone(), Type::Any)]));
+                self.statement(body);
+                self.scopes.pop();
+            }
+        }
+    }
+    fn expression(&mut self, expression: &Expression) -> Type {
+        match expression {
+            Expression::Number(_) | Expression::Float(_) | Expression::Integer(_) => Type::Number,
+            Expression::String(_) => Type::String,
+            Expression::Bytes(_) => Type::Any,
+            Expression::Boolean(_) => Type::Bool,
+            Expression::Nil => Type::Nil,
+            Expression::Group(expr) => self.expression(expr),
+            Expression::Identifier(name) => self.lookup(name),
+            Expression::Get(object, _) => {
+                self.expression(object);
+                Type::Any
+            }
+            Expression::Index(object, index) => {
+                self.expression(object);
+                self.expression(index);
+                Type::Any
+            }
+            Expression::Range(start, end, _) => {
+                let start = self.expression(start);
+                let end = self.expression(end);
+                if !compatible(Type::Number, start) || !compatible(Type::Number, end) {
+                    self.error(format!(
+                        "Range bounds must be numbers, got {start} and {end}."
+                    ));
+                }
+                Type::Any
+            }
+            Expression::NamedArgument(_, value) => self.expression(value),
+            Expression::Tuple(items) => {
+                for item in items {
+                    self.expression(item);
+                }
+                Type::Any
+            }
+            Expression::Pipeline(value, f) => {
+                self.expression(value);
+                self.expression(f);
+                Type::Any
+            }
+            Expression::Optional(object) => {
+                self.expression(object);
+                Type::Any
+            }
+            Expression::Call(callee, args, _) => {
+                self.expression(callee);
+                for arg in args {
+                    self.expression(arg);
+                }
+                Type::Any
+            }
+            Expression::Assign(name, value) => {
+                let actual = self.expression(value);
+                let expected = self.lookup(name);
+                if !compatible(expected, actual) {
+                    self.error(format!(
+                        "Cannot assign {actual} to '{name}' of type {expected}."
+                    ));
+                }
+                actual
+            }
+            Expression::Unary(Operator::Subtract, expr) => match self.expression(expr) {
+                Type::String | Type::Bool => {
+                    self.error("Operand of '-' must be a number.".to_owned());
+                    Type::Any
+                }
+                Type::Nil => Type::Bool,
+                Type::Any => Type::Any,
+                _ => Type::Number,
+            },
+            Expression::Unary(_, expr) => {
+                self.expression(expr);
+                Type::Bool
+            }
+            Expression::Binary(left, op, right) => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                self.binary(op, left, right)
+            }
+        }
+    }
+    fn binary(&mut self, op: &Operator, left: Type, right: Type) -> Type {
+        let symbol = match op {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::EqualEqual | Operator::BangEqual => return Type::Bool,
+            _ => return Type::Any,
+        };
+        match (op, left, right) {
+            (_, Type::Any, _) | (_, _, Type::Any) => Type::Any,
+            (Operator::Add, Type::Number, Type::Number) => Type::Number,
+            (Operator::Add, Type::String, Type::String) => Type::String,
+            (Operator::Add, Type::Nil, _) | (Operator::Add, _, Type::Nil) => Type::Bool,
+            (Operator::Add, _, _) => {
+                self.error(format!(
+                    "Operands of '+' must be two numbers or two strings, got {left} and {right}."
+                ));
+                Type::Any
+            }
+            (
+                Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual,
+                _,
+                _,
+            ) => {
+                if left != Type::Number || right != Type::Number {
+                    self.error(format!(
+                        "Operands of '{symbol}' must be numbers, got {left} and {right}."
+                    ));
+                }
+                Type::Bool
+            }
+            (_, Type::Number, Type::Number) => Type::Number,
+            (_, Type::Nil, _) | (_, _, Type::Nil) => Type::Nil,
+            _ => {
+                self.error(format!(
+                    "Operands of '{symbol}' must be numbers, got {left} and {right}."
+                ));
+                Type::Any
+            }
+        }
+    }
+}
+
+fn compatible(expected: Type, actual: Type) -> bool {
+    expected == actual || expected == Type::Any || actual == Type::Any
+}
+
+#[cfg(test)]
+fn check_source(source: &str) -> Vec<String> {
+    let tokenizer = Tokenizer::new(source.into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    check(&parser.parse_program().unwrap())
+}
+
+#[test]
+fn test_check_operands() {
+    assert_eq!(
+        check_source("print 1 + 2;\nprint \"a\" - 1;\nprint (1 < 2) + 3;"),
+        [
+            "[line 2] Type error: Operands of '-' must be numbers, got String and Number.",
+            "[line 3] Type error: Operands of '+' must be two numbers or two strings, got Bool and Number."
+        ]
+    );
+}
+
+#[test]
+fn test_check_annotations() {
+    let errors = check_source(
+        "var n: Number = 1;
+var s: String = n;
+var dynamic = \"x\";
+n = dynamic;
+{ var n = \"shadowed\"; n = \"ok\"; }
+n = true;
+const flag: Bool = nil;",
+    );
+    assert_eq!(
+        errors,
+        [
+            "[line 2] Type error: Cannot initialize 's' of type String with Number.",
+            "[line 6] Type error: Cannot assign Bool to 'n' of type Number.",
+            "[line 7] Type error: Cannot initialize 'flag' of type Bool with Nil."
+        ]
+    );
+}
+
+#[test]
+fn test_check_negate() {
+    assert_eq!(
+        check_source("var dynamic = \"x\";\nvar s: String = -dynamic;\nvar b: Bool = -1;"),
+        ["[line 3] Type error: Cannot initialize 'b' of type Bool with Number."]
+    );
+}
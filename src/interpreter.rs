@@ -1,21 +1,309 @@
 use crate::{
-    parser::{Expression, Operator, Parser, Statement},
+    bigint::BigInt,
+    encoding, modules,
+    parser::{BindingPattern, Expression, Operator, Parser, Pattern, Statement},
     tokenizer::Tokenizer,
 };
 use anyhow::{anyhow, Context};
 use anyhow::{Ok, Result};
-use std::{collections::HashMap, hash::Hash, ops::Add};
-#[derive(Debug, PartialEq, PartialOrd, Clone)]
+use bytes::{Bytes, BytesMut};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    cmp::Ordering,
+    fmt,
+    hash::Hash,
+    iter,
+    ops::{Add, Index},
+    sync::Arc,
+};
+#[derive(Debug, Clone)]
 pub enum RuntimeValue {
     Number(f64),
+    // Exact integers; arithmetic that overflows i64 moves on to BigInteger
+    Integer(i64),
+    BigInteger(BigInt),
     Boolean(bool),
     String(String),
+    // Binary data; slices share the buffer they were taken from
+    Bytes(Bytes),
+    Error(String, u32),
+    Module(String, Arc<BTreeMap<String, RuntimeValue>>),
+    List(Vec<RuntimeValue>),
+    // start, end, step and whether the end is included
+    Range(f64, f64, f64, bool),
+    Tuple(Vec<RuntimeValue>),
+    // A struct declaration: its name and field names, callable as a constructor
+    StructType(String, Vec<String>),
+    // An instance of a struct, keeping its fields in declaration order
+    Record(String, Vec<(String, RuntimeValue)>),
+    // An enum declaration: its name and each variant's payload field names.
+    // Variants are struct types named like `Shape.Circle`, or records when
+    // they carry no payload
+    EnumType(String, Vec<(String, Vec<String>)>),
+    // A native method looked up on a value, waiting to be called
+    Method(Box<RuntimeValue>, String),
     Nil,
 }
 
+/// The variables in scope, along with which of them were declared `const`.
+#[derive(Debug, Default, Clone)]
+pub struct Environment {
+    values: HashMap<String, RuntimeValue>,
+    constants: HashSet<String>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn get(&self, name: &str) -> Option<&RuntimeValue> {
+        self.values.get(name)
+    }
+    /// Binds `name` to a value that can be reassigned, shadowing any constant.
+    pub fn insert(&mut self, name: String, value: RuntimeValue) {
+        self.constants.remove(&name);
+        self.values.insert(name, value);
+    }
+    pub fn insert_const(&mut self, name: String, value: RuntimeValue) {
+        self.constants.insert(name.clone());
+        self.values.insert(name, value);
+    }
+}
+
+impl Index<&str> for Environment {
+    type Output = RuntimeValue;
+
+    fn index(&self, name: &str) -> &RuntimeValue {
+        &self.values[name]
+    }
+}
+
+impl IntoIterator for Environment {
+    type Item = (String, RuntimeValue);
+    type IntoIter = std::collections::hash_map::IntoIter<String, RuntimeValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuntimeError {
+    #[error("{0}\n[line {1}]")]
+    Error(String, u32),
+    #[error("Uncaught exception: {0}\n[line {1}]")]
+    Thrown(RuntimeValue, u32),
+}
+
+impl fmt::Display for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeValue::Number(val) => write!(f, "{}", val),
+            RuntimeValue::Integer(val) => write!(f, "{}", val),
+            RuntimeValue::BigInteger(val) => write!(f, "{}", val),
+            RuntimeValue::Boolean(val) => write!(f, "{}", val),
+            RuntimeValue::String(val) => write!(f, "{}", val),
+            RuntimeValue::Bytes(val) => write!(f, "b\"{}\"", val.escape_ascii()),
+            RuntimeValue::Error(message, line) => write!(f, "[line {}] Error: {}", line, message),
+            RuntimeValue::Module(path, _) => write!(f, "<module {}>", path),
+            RuntimeValue::List(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            RuntimeValue::Method(_, name) => write!(f, "<native fn {}>", name),
+            RuntimeValue::Tuple(items) if items.len() == 1 => write!(f, "({},)", items[0]),
+            RuntimeValue::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "({})", items.join(", "))
+            }
+            RuntimeValue::StructType(name, _) => write!(f, "<struct {}>", name),
+            RuntimeValue::EnumType(name, _) => write!(f, "<enum {}>", name),
+            // Only variants have dotted names, and those without a payload
+            // print as just `Shape.Empty`
+            RuntimeValue::Record(name, fields) if fields.is_empty() && name.contains('.') => {
+                write!(f, "{}", name)
+            }
+            RuntimeValue::Record(name, fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!("{}: {}", field, value))
+                    .collect();
+                write!(f, "{}({})", name, fields.join(", "))
+            }
+            RuntimeValue::Range(start, end, step, inclusive) => {
+                let op = if *inclusive { "..=" } else { ".." };
+                if *step == 1.0 {
+                    write!(f, "{}{}{}", start, op, end)
+                } else {
+                    write!(f, "({}{}{}).step({})", start, op, end, step)
+                }
+            }
+            RuntimeValue::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+impl PartialEq for RuntimeValue {
+    /// Numbers compare by value whatever their representation, so `1 == 1.0`.
+    fn eq(&self, other: &RuntimeValue) -> bool {
+        if let Some(ordering) = self.compare_numbers(other) {
+            return ordering == Ordering::Equal;
+        }
+        match (self, other) {
+            (RuntimeValue::Boolean(x), RuntimeValue::Boolean(y)) => x == y,
+            (RuntimeValue::String(x), RuntimeValue::String(y)) => x == y,
+            (RuntimeValue::Bytes(x), RuntimeValue::Bytes(y)) => x == y,
+            (RuntimeValue::Error(x, a), RuntimeValue::Error(y, b)) => x == y && a == b,
+            (RuntimeValue::Module(x, a), RuntimeValue::Module(y, b)) => x == y && a == b,
+            (RuntimeValue::List(x), RuntimeValue::List(y)) => x == y,
+            (RuntimeValue::Range(a, b, c, d), RuntimeValue::Range(w, x, y, z)) => {
+                (a, b, c, d) == (w, x, y, z)
+            }
+            (RuntimeValue::Tuple(x), RuntimeValue::Tuple(y)) => x == y,
+            (RuntimeValue::StructType(x, a), RuntimeValue::StructType(y, b)) => x == y && a == b,
+            (RuntimeValue::Record(x, a), RuntimeValue::Record(y, b)) => x == y && a == b,
+            (RuntimeValue::EnumType(x, a), RuntimeValue::EnumType(y, b)) => x == y && a == b,
+            (RuntimeValue::Method(x, a), RuntimeValue::Method(y, b)) => x == y && a == b,
+            (RuntimeValue::Nil, RuntimeValue::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
 impl RuntimeValue {
-    pub fn multiply(self, other: RuntimeValue) -> Result<RuntimeValue> {
+    pub fn is_equal(&self, other: &RuntimeValue) -> bool {
+        self == other
+    }
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RuntimeValue::Number(_) | RuntimeValue::Integer(_) | RuntimeValue::BigInteger(_) => {
+                "Number"
+            }
+            RuntimeValue::Boolean(_) => "Bool",
+            RuntimeValue::String(_) => "String",
+            RuntimeValue::Bytes(_) => "Bytes",
+            RuntimeValue::Error(_, _) => "Error",
+            RuntimeValue::Module(_, _) => "Module",
+            RuntimeValue::List(_) => "List",
+            RuntimeValue::Range(_, _, _, _) => "Range",
+            RuntimeValue::Tuple(_) => "Tuple",
+            RuntimeValue::StructType(_, _) => "Struct",
+            RuntimeValue::Record(_, _) => "Record",
+            RuntimeValue::EnumType(_, _) => "Enum",
+            RuntimeValue::Method(_, _) => "Function",
+            RuntimeValue::Nil => "Nil",
+        }
+    }
+    /// Iteration protocol behind `for (x in value)`: each iterable value type
+    /// hands out its elements here.
+    pub fn iterate(self) -> Result<Box<dyn Iterator<Item = RuntimeValue>>> {
+        match self {
+            RuntimeValue::String(s) => Ok(Box::new(
+                s.chars()
+                    .map(|c| RuntimeValue::String(c.to_string()))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+            RuntimeValue::List(items) | RuntimeValue::Tuple(items) => {
+                Ok(Box::new(items.into_iter()))
+            }
+            RuntimeValue::Bytes(bytes) => Ok(Box::new(
+                bytes
+                    .into_iter()
+                    .map(|byte| RuntimeValue::Integer(byte.into())),
+            )),
+            RuntimeValue::Range(start, end, step, inclusive) => Ok(Box::new(
+                (0u64..)
+                    .map(move |i| start + i as f64 * step)
+                    .take_while(move |x| if inclusive { *x <= end } else { *x < end })
+                    .map(RuntimeValue::literal),
+            )),
+            value => Err(anyhow!(
+                "Value of type {} is not iterable.",
+                value.type_name()
+            )),
+        }
+    }
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, RuntimeValue::Nil | RuntimeValue::Boolean(false))
+    }
+    /// Value of a number literal: whole numbers that a float represents
+    /// exactly become integers, anything else stays a float.
+    pub fn literal(x: f64) -> RuntimeValue {
+        if x.fract() == 0.0 && x.abs() <= (1u64 << 53) as f64 {
+            RuntimeValue::Integer(x as i64)
+        } else {
+            RuntimeValue::Number(x)
+        }
+    }
+    pub fn from_big(n: BigInt) -> RuntimeValue {
+        match n.to_i64() {
+            Some(n) => RuntimeValue::Integer(n),
+            None => RuntimeValue::BigInteger(n),
+        }
+    }
+    fn to_big(&self) -> Option<BigInt> {
+        match self {
+            RuntimeValue::Integer(x) => Some(BigInt::from(*x)),
+            RuntimeValue::BigInteger(x) => Some(x.clone()),
+            _ => None,
+        }
+    }
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            RuntimeValue::Number(x) => Some(*x),
+            RuntimeValue::Integer(x) => Some(*x as f64),
+            RuntimeValue::BigInteger(x) => Some(x.to_f64()),
+            _ => None,
+        }
+    }
+    /// Turns integers into floats for operations that mix the two, leaving
+    /// every other value untouched.
+    fn into_float(self) -> RuntimeValue {
+        match self.as_float() {
+            Some(x) => RuntimeValue::Number(x),
+            None => self,
+        }
+    }
+    /// Runs an arithmetic operation exactly when both operands are integers,
+    /// falling back to big integers when `small` overflows.
+    fn integer_op(
+        &self,
+        other: &RuntimeValue,
+        small: fn(i64, i64) -> Option<i64>,
+        big: fn(&BigInt, &BigInt) -> BigInt,
+    ) -> Option<RuntimeValue> {
+        if let (RuntimeValue::Integer(x), RuntimeValue::Integer(y)) = (self, other) {
+            if let Some(result) = small(*x, *y) {
+                return Some(RuntimeValue::Integer(result));
+            }
+        }
+        let (x, y) = (self.to_big()?, other.to_big()?);
+        Some(RuntimeValue::from_big(big(&x, &y)))
+    }
+    fn compare_numbers(&self, other: &RuntimeValue) -> Option<Ordering> {
         match (self, other) {
+            (RuntimeValue::Integer(x), RuntimeValue::Integer(y)) => Some(x.cmp(y)),
+            (
+                RuntimeValue::Integer(_) | RuntimeValue::BigInteger(_),
+                RuntimeValue::Integer(_) | RuntimeValue::BigInteger(_),
+            ) => Some(self.to_big()?.cmp(&other.to_big()?)),
+            _ => self.as_float()?.partial_cmp(&other.as_float()?),
+        }
+    }
+    fn compare(&self, other: &RuntimeValue, test: fn(Ordering) -> bool) -> Result<RuntimeValue> {
+        match (self.as_float(), other.as_float()) {
+            (Some(_), Some(_)) => Ok(RuntimeValue::Boolean(
+                self.compare_numbers(other).is_some_and(test),
+            )),
+            _ => Err(anyhow!("Operands must be numbers.")),
+        }
+    }
+    pub fn multiply(self, other: RuntimeValue) -> Result<RuntimeValue> {
+        if let Some(product) = self.integer_op(&other, i64::checked_mul, |x, y| x * y) {
+            return Ok(product);
+        }
+        match (self.into_float(), other.into_float()) {
             (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Number(x * y)),
             (RuntimeValue::Number(x), RuntimeValue::Boolean(y)) => {
                 Err(anyhow!(format!("Cannot multiply f64:{x} with bool:{y}")))
@@ -43,10 +331,11 @@ impl RuntimeValue {
             }
             (RuntimeValue::Nil, _) => Ok(RuntimeValue::Nil),
             (_, RuntimeValue::Nil) => Ok(RuntimeValue::Nil),
+            _ => Err(anyhow!("Operands must be numbers.")),
         }
     }
     pub fn divide(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
+        match (self.into_float(), other.into_float()) {
             (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Number(x / y)),
             (RuntimeValue::Number(x), RuntimeValue::Boolean(y)) => {
                 Err(anyhow!(format!("Cannot multiply f64:{x} with bool:{y}")))
@@ -74,10 +363,14 @@ impl RuntimeValue {
             }
             (RuntimeValue::Nil, _) => Ok(RuntimeValue::Nil),
             (_, RuntimeValue::Nil) => Ok(RuntimeValue::Nil),
+            _ => Err(anyhow!("Operands must be numbers.")),
         }
     }
     pub fn subtract(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
+        if let Some(difference) = self.integer_op(&other, i64::checked_sub, |x, y| x - y) {
+            return Ok(difference);
+        }
+        match (self.into_float(), other.into_float()) {
             (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Number(x - y)),
             (RuntimeValue::Number(x), RuntimeValue::Boolean(y)) => {
                 Err(anyhow!(format!("Cannot multiply f64:{x} with bool:{y}")))
@@ -105,13 +398,29 @@ impl RuntimeValue {
             }
             (RuntimeValue::Nil, _) => Ok(RuntimeValue::Nil),
             (_, RuntimeValue::Nil) => Ok(RuntimeValue::Nil),
+            _ => Err(anyhow!("Operands must be numbers.")),
         }
     }
     fn negate(&self) -> Result<RuntimeValue> {
         match (self) {
             (RuntimeValue::Number(x)) => Ok(RuntimeValue::Number(-x)),
+            RuntimeValue::Integer(x) => Ok(match x.checked_neg() {
+                Some(x) => RuntimeValue::Integer(x),
+                None => RuntimeValue::from_big(-&BigInt::from(*x)),
+            }),
+            RuntimeValue::BigInteger(x) => Ok(RuntimeValue::from_big(-x)),
             (RuntimeValue::Boolean(x)) => Err(anyhow!("Can not take the negative of a boolean.")),
             (RuntimeValue::String(x)) => Err(anyhow!("Operand must be a number.")),
+            RuntimeValue::Bytes(_)
+            | RuntimeValue::Error(_, _)
+            | RuntimeValue::Module(_, _)
+            | RuntimeValue::List(_)
+            | RuntimeValue::Range(_, _, _, _)
+            | RuntimeValue::Tuple(_)
+            | RuntimeValue::StructType(_, _)
+            | RuntimeValue::Record(_, _)
+            | RuntimeValue::EnumType(_, _)
+            | RuntimeValue::Method(_, _) => Err(anyhow!("Operand must be a number.")),
             RuntimeValue::Nil => Ok(RuntimeValue::Boolean(false)),
         }
     }
@@ -125,11 +434,24 @@ impl RuntimeValue {
             RuntimeValue::Number(x) => Ok(RuntimeValue::Boolean(x > 0.0)),
             RuntimeValue::Boolean(x) => Ok(RuntimeValue::Boolean(x)),
             RuntimeValue::String(x) => Ok(RuntimeValue::Boolean(false)),
+            value @ (RuntimeValue::Integer(_) | RuntimeValue::BigInteger(_)) => {
+                value.into_float().not_and()
+            }
+            RuntimeValue::Bytes(_)
+            | RuntimeValue::Error(_, _)
+            | RuntimeValue::Module(_, _)
+            | RuntimeValue::List(_)
+            | RuntimeValue::Range(_, _, _, _)
+            | RuntimeValue::Tuple(_)
+            | RuntimeValue::StructType(_, _)
+            | RuntimeValue::Record(_, _)
+            | RuntimeValue::EnumType(_, _)
+            | RuntimeValue::Method(_, _) => Ok(RuntimeValue::Boolean(false)),
             RuntimeValue::Nil => Ok(RuntimeValue::Boolean(true)),
         }
     }
     pub fn and(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
+        match (self.into_float(), other.into_float()) {
             (RuntimeValue::Number(x), RuntimeValue::Number(y)) => {
                 Ok(RuntimeValue::Boolean((x > 0.0) && (y > 0.0)))
             }
@@ -162,38 +484,30 @@ impl RuntimeValue {
             (RuntimeValue::Nil, RuntimeValue::Boolean(_)) => Ok(RuntimeValue::Boolean(false)),
             (RuntimeValue::Nil, RuntimeValue::String(_)) => Ok(RuntimeValue::Boolean(false)),
             (RuntimeValue::Nil, RuntimeValue::Nil) => Ok(RuntimeValue::Boolean(false)),
+            (x, y) => Ok(RuntimeValue::Boolean(x.is_truthy() && y.is_truthy())),
         }
     }
     pub fn less_than(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
-            (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Boolean(x < y)),
-            _ => Err(anyhow!(format!("Operands must be numbers."))),
-        }
+        self.compare(&other, Ordering::is_lt)
     }
     pub fn less_than_equal(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
-            (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Boolean(x <= y)),
-            _ => Err(anyhow!(format!("Operands must be numbers."))),
-        }
+        self.compare(&other, Ordering::is_le)
     }
     pub fn greater(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
-            (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Boolean(x > y)),
-            _ => Err(anyhow!(format!("Operands must be numbers."))),
-        }
+        self.compare(&other, Ordering::is_gt)
     }
     pub fn greater_equal(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
-            (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Boolean(x >= y)),
-            _ => Err(anyhow!(format!("Operands must be numbers."))),
-        }
+        self.compare(&other, Ordering::is_ge)
     }
 }
 impl Add for RuntimeValue {
     type Output = Result<RuntimeValue>;
 
     fn add(self, other: RuntimeValue) -> Result<RuntimeValue> {
-        match (self, other) {
+        if let Some(sum) = self.integer_op(&other, i64::checked_add, |x, y| x + y) {
+            return Ok(sum);
+        }
+        match (self.into_float(), other.into_float()) {
             (RuntimeValue::Number(x), RuntimeValue::Number(y)) => Ok(RuntimeValue::Number(x + y)),
             (RuntimeValue::Number(x), RuntimeValue::Boolean(y)) => {
                 Err(anyhow!(format!("Cannot add f64:{x} to bool:{y}")))
@@ -217,6 +531,12 @@ impl Add for RuntimeValue {
                 Err(anyhow!(format!("Cannot add String:{x} to Boolean:{y}")))
             }
             (RuntimeValue::String(x), RuntimeValue::String(y)) => Ok(RuntimeValue::String(x + &y)),
+            (RuntimeValue::Bytes(x), RuntimeValue::Bytes(y)) => {
+                let mut joined = BytesMut::with_capacity(x.len() + y.len());
+                joined.extend_from_slice(&x);
+                joined.extend_from_slice(&y);
+                Ok(RuntimeValue::Bytes(joined.freeze()))
+            }
             (RuntimeValue::Number(_), RuntimeValue::Nil) => Ok(RuntimeValue::Boolean(false)),
             (RuntimeValue::Boolean(_), RuntimeValue::Nil) => Ok(RuntimeValue::Boolean(false)),
             (RuntimeValue::String(_), RuntimeValue::Nil) => Ok(RuntimeValue::Boolean(false)),
@@ -224,26 +544,20 @@ impl Add for RuntimeValue {
             (RuntimeValue::Nil, RuntimeValue::Boolean(_)) => Ok(RuntimeValue::Boolean(false)),
             (RuntimeValue::Nil, RuntimeValue::String(_)) => Ok(RuntimeValue::Boolean(false)),
             (RuntimeValue::Nil, RuntimeValue::Nil) => Ok(RuntimeValue::Boolean(false)),
+            _ => Err(anyhow!("Operands must be two numbers or two strings.")),
         }
     }
 }
 
 impl Statement {
-    pub fn eval(&self, variables: &mut HashMap<String, RuntimeValue>) -> Result<()> {
+    pub fn eval(&self, variables: &mut Environment) -> Result<()> {
         match self {
-            Statement::Print(x) => {
-                let value = x.eval(variables)?;
-                match value {
-                    RuntimeValue::Number(val) => {
-                        println!("{}", val);
-                    }
-                    RuntimeValue::Boolean(val) => println!("{}", val),
-                    RuntimeValue::String(val) => println!("{}", val),
-                    RuntimeValue::Nil =>   println!("nil"),
-                }
+            Statement::Print(x, line) => {
+                let value = x.eval(variables).map_err(|e| at_line(e, *line))?;
+                println!("{}", value);
             }
-            Statement::ExprStmt(s) => {
-                s.eval(variables)?;
+            Statement::ExprStmt(s, line) => {
+                s.eval(variables).map_err(|e| at_line(e, *line))?;
             }
 
             Statement::Program(program) => {
@@ -251,22 +565,623 @@ impl Statement {
                     statement.eval(variables)?;
                 }
             }
-            Statement::Declaration(name, var) => {
-                let value = var.eval(variables)?;  
+            Statement::Declaration(name, _, var, line) => {
+                let value = var.eval(variables).map_err(|e| at_line(e, *line))?;
                 variables.insert(name.clone(), value);
               //  println!("assigning {:#?} to {name}", var.eval(variables)?);
             },
+            Statement::Const(name, _, value, line) => {
+                let value = value.eval(variables).map_err(|e| at_line(e, *line))?;
+                variables.insert_const(name.clone(), value);
+            }
+            Statement::Match(subject, arms, line) => {
+                let value = subject.eval(variables).map_err(|e| at_line(e, *line))?;
+                for arm in arms.iter() {
+                    // Names bound by the arm's pattern only live until it is done
+                    let names = arm.names();
+                    let enclosing = (!names.is_empty()).then(|| variables.clone());
+                    let mut matched = false;
+                    for pattern in arm.patterns.iter() {
+                        if pattern
+                            .matches(&value, variables)
+                            .map_err(|e| at_line(e, *line))?
+                        {
+                            matched = true;
+                            break;
+                        }
+                    }
+                    let taken = match (&arm.guard, matched) {
+                        (_, false) => Ok(false),
+                        (Some(guard), true) => guard
+                            .eval(variables)
+                            .map(|g| g.is_truthy())
+                            .map_err(|e| at_line(e, *line)),
+                        (None, true) => Ok(true),
+                    };
+                    let result = taken.and_then(|taken| {
+                        if taken {
+                            arm.body.eval(variables)?;
+                        }
+                        Ok(taken)
+                    });
+                    if let Some(enclosing) = enclosing {
+                        end_scope(variables, enclosing, names.into_iter());
+                    }
+                    if result? {
+                        break;
+                    }
+                }
+            }
+            Statement::Block(statements) => eval_block(statements, variables)?,
+            Statement::Throw(value, line) => {
+                let value = value.eval(variables).map_err(|e| at_line(e, *line))?;
+                return match value {
+                    RuntimeValue::Error(message, line) => {
+                        Err(RuntimeError::Error(message, line).into())
+                    }
+                    value => Err(RuntimeError::Thrown(value, *line).into()),
+                };
+            }
+            Statement::Import(path, name, line) => {
+                let module = modules::import(path).map_err(|e| at_line(e, *line))?;
+                variables.insert(name.clone(), module);
+            }
+            Statement::Struct(name, fields) => {
+                let value = RuntimeValue::StructType(name.clone(), fields.clone());
+                variables.insert(name.clone(), value);
+            }
+            Statement::Enum(name, variants) => {
+                let value = RuntimeValue::EnumType(name.clone(), variants.clone());
+                variables.insert(name.clone(), value);
+            }
+            Statement::DestructureDeclaration(pattern, value, line) => {
+                let value = value.eval(variables).map_err(|e| at_line(e, *line))?;
+                destructure(pattern, value, variables, true).map_err(|e| at_line(e, *line))?;
+            }
+            Statement::DestructureAssign(pattern, value, line) => {
+                let value = value.eval(variables).map_err(|e| at_line(e, *line))?;
+                destructure(pattern, value, variables, false).map_err(|e| at_line(e, *line))?;
+            }
+            Statement::ForIn(name, iterable, body, line) => {
+                let items = iterable
+                    .eval(variables)
+                    .and_then(RuntimeValue::iterate)
+                    .map_err(|e| at_line(e, *line))?;
+                let enclosing = variables.clone();
+                let mut result = Ok(());
+                for item in items {
+                    variables.insert(name.clone(), item);
+                    result = body.eval(variables);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                end_scope(variables, enclosing, std::iter::once(name));
+                result?;
+            }
+            Statement::Try(body, catch, finally) => {
+                let mut result = eval_block(body, variables);
+                if let (Err(error), Some((name, handler))) = (&result, catch) {
+                    let value = match error.downcast_ref::<RuntimeError>() {
+                        Some(RuntimeError::Thrown(value, _)) => value.clone(),
+                        Some(RuntimeError::Error(message, line)) => {
+                            RuntimeValue::Error(message.clone(), *line)
+                        }
+                        None => RuntimeValue::Error(error.to_string(), 0),
+                    };
+                    let enclosing = variables.clone();
+                    variables.insert(name.clone(), value);
+                    result = eval_block(handler, variables);
+                    end_scope(variables, enclosing, std::iter::once(name));
+                }
+                if let Some(finally) = finally {
+                    eval_block(finally, variables)?;
+                }
+                result?;
+            }
         }
         Ok(())
     }
+    /// Names the statement binds in the scope it appears in.
+    fn declared_names(&self) -> Vec<&String> {
+        match self {
+            Statement::Declaration(name, _, _, _)
+            | Statement::Const(name, _, _, _)
+            | Statement::Import(_, name, _)
+            | Statement::Struct(name, _)
+            | Statement::Enum(name, _) => vec![name],
+            Statement::DestructureDeclaration(pattern, _, _) => pattern.names(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn destructure(
+    pattern: &BindingPattern,
+    value: RuntimeValue,
+    variables: &mut Environment,
+    declare: bool,
+) -> Result<()> {
+    match pattern {
+        BindingPattern::Name(name) if declare => {
+            variables.insert(name.clone(), value);
+            Ok(())
+        }
+        BindingPattern::Name(name) => assign(variables, name, value),
+        BindingPattern::List(items, rest) => {
+            let type_name = value.type_name();
+            let is_string = matches!(value, RuntimeValue::String(_));
+            let is_list = matches!(value, RuntimeValue::List(_));
+            let elements = value
+                .iterate()
+                .map_err(|_| anyhow!("Cannot destructure {type_name} as a list."))?
+                .collect::<Vec<_>>();
+            if elements.len() < items.len() || (rest.is_none() && elements.len() > items.len()) {
+                let at_least = if rest.is_some() { "at least " } else { "" };
+                return Err(anyhow!(
+                    "Expected {at_least}{} elements to destructure but got {}.",
+                    items.len(),
+                    elements.len()
+                ));
+            }
+            let mut elements = elements.into_iter();
+            for (item, element) in items.iter().zip(elements.by_ref()) {
+                destructure(item, element, variables, declare)?;
+            }
+            match rest {
+                Some(rest) if is_string => {
+                    let remaining = elements.map(|element| element.to_string()).collect();
+                    let rest_pattern = BindingPattern::Name(rest.clone());
+                    destructure(
+                        &rest_pattern,
+                        RuntimeValue::String(remaining),
+                        variables,
+                        declare,
+                    )
+                }
+                Some(rest) if is_list => {
+                    let rest_pattern = BindingPattern::Name(rest.clone());
+                    let remaining = RuntimeValue::List(elements.collect());
+                    destructure(&rest_pattern, remaining, variables, declare)
+                }
+                Some(_) => Err(anyhow!(
+                    "Rest elements can only collect the rest of a string or list."
+                )),
+                None => Ok(()),
+            }
+        }
+        BindingPattern::Tuple(items) => {
+            let RuntimeValue::Tuple(elements) = value else {
+                return Err(anyhow!(
+                    "Cannot destructure {} as a tuple.",
+                    value.type_name()
+                ));
+            };
+            if elements.len() != items.len() {
+                return Err(anyhow!(
+                    "Expected {} elements to destructure but got {}.",
+                    items.len(),
+                    elements.len()
+                ));
+            }
+            for (item, element) in items.iter().zip(elements) {
+                destructure(item, element, variables, declare)?;
+            }
+            Ok(())
+        }
+        BindingPattern::Map(entries) => {
+            for (key, target) in entries {
+                let member = get_property(value.clone(), key).map_err(|_| {
+                    anyhow!(
+                        "Missing key '{key}' when destructuring {}.",
+                        value.type_name()
+                    )
+                })?;
+                destructure(target, member, variables, declare)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn assign(variables: &mut Environment, name: &str, value: RuntimeValue) -> Result<()> {
+    if variables.constants.contains(name) {
+        return Err(anyhow!("Cannot assign to constant '{name}'."));
+    }
+    let slot = variables
+        .values
+        .get_mut(name)
+        .context(format!("Undefined variable '{name}'."))?;
+    *slot = value;
+    Ok(())
+}
+
+fn get_property(object: RuntimeValue, name: &str) -> Result<RuntimeValue> {
+    match (object, name) {
+        (RuntimeValue::Error(message, _), "message") => Ok(RuntimeValue::String(message)),
+        (RuntimeValue::Error(_, line), "line") => Ok(RuntimeValue::Integer(line.into())),
+        (RuntimeValue::Module(path, members), name) => members
+            .get(name)
+            .cloned()
+            .context(format!("Undefined name '{name}' in module '{path}'.")),
+        (RuntimeValue::String(s), "length") => Ok(RuntimeValue::Integer(s.chars().count() as i64)),
+        (
+            object @ RuntimeValue::String(_),
+            "upper" | "lower" | "trim" | "split" | "contains" | "indexOf" | "replace" | "slice"
+            | "toBytes" | "decodeHex" | "decodeBase64",
+        ) => Ok(RuntimeValue::Method(Box::new(object), name.to_owned())),
+        (RuntimeValue::Bytes(bytes), "length") => Ok(RuntimeValue::Integer(bytes.len() as i64)),
+        (object @ RuntimeValue::Bytes(_), "toString" | "toHex" | "toBase64") => {
+            Ok(RuntimeValue::Method(Box::new(object), name.to_owned()))
+        }
+        (RuntimeValue::Tuple(items), name) if name.bytes().all(|b| b.is_ascii_digit()) => {
+            let length = items.len();
+            name.parse()
+                .ok()
+                .and_then(|i: usize| items.into_iter().nth(i))
+                .context(format!(
+                    "Tuple index {name} is out of range for length {length}."
+                ))
+        }
+        (RuntimeValue::Record(record, fields), name) => {
+            match fields.iter().position(|(field, _)| field == name) {
+                Some(i) => Ok(fields.into_iter().nth(i).map(|(_, value)| value).unwrap()),
+                None if name == "with" => Ok(RuntimeValue::Method(
+                    Box::new(RuntimeValue::Record(record, fields)),
+                    name.to_owned(),
+                )),
+                None => Err(anyhow!("Undefined field '{name}' on {record}.")),
+            }
+        }
+        (RuntimeValue::EnumType(enum_name, variants), name) => {
+            let (variant, fields) = variants
+                .into_iter()
+                .find(|(variant, _)| variant == name)
+                .context(format!("Undefined variant '{name}' on {enum_name}."))?;
+            let variant = format!("{enum_name}.{variant}");
+            match fields.is_empty() {
+                true => Ok(RuntimeValue::Record(variant, Vec::new())),
+                false => Ok(RuntimeValue::StructType(variant, fields)),
+            }
+        }
+        (object @ RuntimeValue::Range(_, _, _, _), "step") => {
+            Ok(RuntimeValue::Method(Box::new(object), name.to_owned()))
+        }
+        (_, name) => Err(anyhow!("Undefined property '{name}'.")),
+    }
+}
+
+fn get_index(object: RuntimeValue, index: RuntimeValue) -> Result<RuntimeValue> {
+    let position = |length: usize| match index.clone().into_float() {
+        RuntimeValue::Number(i) if i.fract() == 0.0 && 0.0 <= i && i < length as f64 => {
+            Ok(i as usize)
+        }
+        RuntimeValue::Number(i) if i.fract() == 0.0 => {
+            Err(anyhow!("Index {i} is out of range for length {length}."))
+        }
+        _ => Err(anyhow!("Index must be an integer, got {index}.")),
+    };
+    match (object, &index) {
+        (RuntimeValue::String(s), RuntimeValue::Range(_, _, _, _)) => {
+            let chars: Vec<char> = s.chars().collect();
+            let positions = slice_positions(&index, chars.len())?;
+            Ok(RuntimeValue::String(positions.map(|i| chars[i]).collect()))
+        }
+        (RuntimeValue::List(items), RuntimeValue::Range(_, _, _, _)) => {
+            let positions = slice_positions(&index, items.len())?;
+            Ok(RuntimeValue::List(
+                positions.map(|i| items[i].clone()).collect(),
+            ))
+        }
+        (RuntimeValue::Bytes(bytes), RuntimeValue::Range(_, _, _, _)) => {
+            let (start, end, step) = slice_bounds(&index, bytes.len())?;
+            if step == 1 {
+                return Ok(RuntimeValue::Bytes(bytes.slice(start..end)));
+            }
+            Ok(RuntimeValue::Bytes(
+                (start..end).step_by(step).map(|i| bytes[i]).collect(),
+            ))
+        }
+        (RuntimeValue::String(s), _) => {
+            let i = position(s.chars().count())?;
+            Ok(RuntimeValue::String(
+                s.chars().nth(i).unwrap_or_default().to_string(),
+            ))
+        }
+        (RuntimeValue::List(mut items), _) => {
+            let i = position(items.len())?;
+            Ok(items.swap_remove(i))
+        }
+        (RuntimeValue::Bytes(bytes), _) => {
+            let i = position(bytes.len())?;
+            Ok(RuntimeValue::Integer(bytes[i].into()))
+        }
+        (object, _) => Err(anyhow!("Cannot index into {}.", object.type_name())),
+    }
+}
+
+/// Positions a range selects from a sequence of `length` elements.
+fn slice_positions(range: &RuntimeValue, length: usize) -> Result<impl Iterator<Item = usize>> {
+    let (start, end, step) = slice_bounds(range, length)?;
+    Ok((start..end).step_by(step))
+}
+
+/// Start, end and step of a range used as a slice, with the end exclusive.
+fn slice_bounds(range: &RuntimeValue, length: usize) -> Result<(usize, usize, usize)> {
+    let RuntimeValue::Range(start, end, step, inclusive) = *range else {
+        return Err(anyhow!("Expected a range, got {range}."));
+    };
+    if step.fract() != 0.0 {
+        return Err(anyhow!("Slice step must be an integer, got {step}."));
+    }
+    let start = slice_index(&RuntimeValue::Number(start), length)?;
+    let mut end = slice_index(&RuntimeValue::Number(end), length)?;
+    if inclusive {
+        end = (end + 1).min(length);
+    }
+    Ok((start, end.max(start), step as usize))
+}
+
+/// Call arguments in source order, with the name of each named argument.
+type Arguments = Vec<(Option<String>, RuntimeValue)>;
+
+fn eval_arguments(args: &[Expression], variables: &mut Environment) -> Result<Arguments> {
+    args.iter()
+        .map(|arg| match arg {
+            Expression::NamedArgument(name, value) => {
+                Ok((Some(name.clone()), value.eval(variables)?))
+            }
+            arg => Ok((None, arg.eval(variables)?)),
+        })
+        .collect()
+}
+
+fn positional(args: Arguments) -> Result<Vec<RuntimeValue>> {
+    args.into_iter()
+        .map(|(name, value)| match name {
+            Some(name) => Err(anyhow!("Unexpected named argument '{name}'.")),
+            None => Ok(value),
+        })
+        .collect()
+}
+
+/// Builds a record of struct `name`, assigning `args` to `fields` in order or
+/// by name. Fields still `None` afterwards have no value and are an error.
+fn struct_fields(
+    name: &str,
+    mut fields: Vec<(String, Option<RuntimeValue>)>,
+    args: Arguments,
+) -> Result<RuntimeValue> {
+    let mut next = 0;
+    for (arg_name, value) in args {
+        let i = match arg_name {
+            Some(arg_name) => fields
+                .iter()
+                .position(|(field, _)| *field == arg_name)
+                .context(format!("Undefined field '{arg_name}' on {name}."))?,
+            None if next < fields.len() => next,
+            None => return Err(anyhow!("Expected {} arguments but got more.", fields.len())),
+        };
+        next = i + 1;
+        fields[i].1 = Some(value);
+    }
+    let fields = fields
+        .into_iter()
+        .map(|(field, value)| {
+            let value = value.context(format!("Missing field '{field}' for {name}."))?;
+            Ok((field, value))
+        })
+        .collect::<Result<_>>()?;
+    Ok(RuntimeValue::Record(name.to_owned(), fields))
+}
+
+fn call(callee: RuntimeValue, args: Arguments) -> Result<RuntimeValue> {
+    match callee {
+        RuntimeValue::StructType(name, fields) => {
+            let fields = fields.into_iter().map(|field| (field, None)).collect();
+            struct_fields(&name, fields, args)
+        }
+        RuntimeValue::Method(receiver, name) => match *receiver {
+            RuntimeValue::Record(record, fields) => {
+                if args.iter().any(|(name, _)| name.is_none()) {
+                    return Err(anyhow!("Arguments to 'with' must be named."));
+                }
+                let fields = fields
+                    .into_iter()
+                    .map(|(field, value)| (field, Some(value)))
+                    .collect();
+                struct_fields(&record, fields, args)
+            }
+            RuntimeValue::String(s) => string_method(&s, &name, positional(args)?),
+            RuntimeValue::Bytes(bytes) => bytes_method(&bytes, &name, positional(args)?),
+            RuntimeValue::Range(start, end, _, inclusive) => match positional(args)?.as_slice() {
+                [step] => match step.as_float() {
+                    Some(x) if x > 0.0 => Ok(RuntimeValue::Range(start, end, x, inclusive)),
+                    _ => Err(anyhow!("Range step must be a positive number, got {step}.")),
+                },
+                args => Err(anyhow!("Expected 1 arguments but got {}.", args.len())),
+            },
+            receiver => Err(anyhow!(
+                "Undefined method '{name}' on {}.",
+                receiver.type_name()
+            )),
+        },
+        _ => Err(anyhow!("Can only call functions and classes.")),
+    }
+}
+
+/// Native methods on strings. Positions count Unicode scalar values, not bytes.
+fn string_method(s: &str, name: &str, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    let arity = match name {
+        "upper" | "lower" | "trim" | "toBytes" | "decodeHex" | "decodeBase64" => 0,
+        "split" | "contains" | "indexOf" => 1,
+        _ => 2,
+    };
+    if args.len() != arity {
+        return Err(anyhow!(
+            "Expected {arity} arguments but got {}.",
+            args.len()
+        ));
+    }
+    let string_arg = |index: usize| match &args[index] {
+        RuntimeValue::String(arg) => Ok(arg.as_str()),
+        arg => Err(anyhow!(
+            "Argument to '{name}' must be a string, got {}.",
+            arg.type_name()
+        )),
+    };
+    match name {
+        "upper" => Ok(RuntimeValue::String(s.to_uppercase())),
+        "lower" => Ok(RuntimeValue::String(s.to_lowercase())),
+        "trim" => Ok(RuntimeValue::String(s.trim().to_owned())),
+        "split" => {
+            let parts: Vec<RuntimeValue> = match string_arg(0)? {
+                "" => s
+                    .chars()
+                    .map(|c| RuntimeValue::String(c.to_string()))
+                    .collect(),
+                sep => s
+                    .split(sep)
+                    .map(|part| RuntimeValue::String(part.into()))
+                    .collect(),
+            };
+            Ok(RuntimeValue::List(parts))
+        }
+        "contains" => Ok(RuntimeValue::Boolean(s.contains(string_arg(0)?))),
+        "indexOf" => {
+            let index = s
+                .find(string_arg(0)?)
+                .map(|byte| s[..byte].chars().count() as i64);
+            Ok(RuntimeValue::Integer(index.unwrap_or(-1)))
+        }
+        "replace" => Ok(RuntimeValue::String(
+            s.replace(string_arg(0)?, string_arg(1)?),
+        )),
+        "slice" => {
+            let length = s.chars().count();
+            let start = slice_index(&args[0], length)?;
+            let end = slice_index(&args[1], length)?.max(start);
+            Ok(RuntimeValue::String(
+                s.chars().skip(start).take(end - start).collect(),
+            ))
+        }
+        "toBytes" => Ok(RuntimeValue::Bytes(Bytes::copy_from_slice(s.as_bytes()))),
+        "decodeHex" => Ok(RuntimeValue::Bytes(encoding::hex_decode(s)?.into())),
+        "decodeBase64" => Ok(RuntimeValue::Bytes(encoding::base64_decode(s)?.into())),
+        _ => Err(anyhow!("Undefined property '{name}'.")),
+    }
+}
+
+fn bytes_method(bytes: &Bytes, name: &str, args: Vec<RuntimeValue>) -> Result<RuntimeValue> {
+    if !args.is_empty() {
+        return Err(anyhow!("Expected 0 arguments but got {}.", args.len()));
+    }
+    match name {
+        "toString" => String::from_utf8(bytes.to_vec())
+            .map(RuntimeValue::String)
+            .map_err(|_| anyhow!("Bytes are not valid UTF-8.")),
+        "toHex" => Ok(RuntimeValue::String(encoding::hex_encode(bytes))),
+        "toBase64" => Ok(RuntimeValue::String(encoding::base64_encode(bytes))),
+        _ => Err(anyhow!("Undefined property '{name}'.")),
+    }
+}
+
+/// Resolves a slice bound to a position, counting negative indices from
+/// the end and clamping to the sequence's length.
+fn slice_index(index: &RuntimeValue, length: usize) -> Result<usize> {
+    match index.clone().into_float() {
+        RuntimeValue::Number(i) if i.fract() == 0.0 => {
+            let i = if i < 0.0 { i + length as f64 } else { i };
+            Ok(i.clamp(0.0, length as f64) as usize)
+        }
+        index => Err(anyhow!("Index must be an integer, got {index}.")),
+    }
+}
+
+/// Attaches the line of the failing statement to errors raised while evaluating it.
+fn at_line(error: anyhow::Error, line: u32) -> anyhow::Error {
+    if error.is::<RuntimeError>() {
+        error
+    } else {
+        RuntimeError::Error(error.to_string(), line).into()
+    }
+}
+
+fn eval_block(statements: &[Statement], variables: &mut Environment) -> Result<()> {
+    let enclosing = variables.clone();
+    let result = statements
+        .iter()
+        .try_for_each(|statement| statement.eval(variables));
+    let declared = statements.iter().flat_map(Statement::declared_names);
+    end_scope(variables, enclosing, declared);
+    result
+}
+
+/// Drops variables declared inside a scope and restores the ones it shadowed.
+fn end_scope<'a>(
+    variables: &mut Environment,
+    enclosing: Environment,
+    declared: impl Iterator<Item = &'a String>,
+) {
+    variables
+        .values
+        .retain(|name, _| enclosing.values.contains_key(name));
+    for name in declared {
+        if let Some(value) = enclosing.values.get(name) {
+            variables.values.insert(name.clone(), value.clone());
+        }
+    }
+    // Only declarations change constness, and those are undone above
+    variables.constants = enclosing.constants;
+}
+impl Pattern {
+    pub fn matches(&self, value: &RuntimeValue, variables: &mut Environment) -> Result<bool> {
+        match (self, value) {
+            (Pattern::Wildcard, _) => Ok(true),
+            (Pattern::Literal(literal), _) => Ok(literal.eval(variables)?.is_equal(value)),
+            (Pattern::Range(low, high, inclusive), value) => match value.as_float() {
+                Some(x) if *inclusive => Ok(*low <= x && x <= *high),
+                Some(x) => Ok(*low <= x && x < *high),
+                None => Ok(false),
+            },
+            (Pattern::Variant(enum_name, variant, fields), value) => {
+                let enum_type = Expression::Identifier(enum_name.clone()).eval(variables)?;
+                match get_property(enum_type, variant)? {
+                    RuntimeValue::StructType(name, payload) => {
+                        if !fields.is_empty() && fields.len() != payload.len() {
+                            return Err(anyhow!(
+                                "Expected {} fields for {name} but got {}.",
+                                payload.len(),
+                                fields.len()
+                            ));
+                        }
+                        let RuntimeValue::Record(record, values) = value else {
+                            return Ok(false);
+                        };
+                        if *record != name {
+                            return Ok(false);
+                        }
+                        for (field, (_, value)) in fields.iter().zip(values) {
+                            if field != "_" {
+                                variables.insert(field.clone(), value.clone());
+                            }
+                        }
+                        Ok(true)
+                    }
+                    expected if fields.is_empty() => Ok(expected.is_equal(value)),
+                    expected => Err(anyhow!("{expected} has no fields to bind.")),
+                }
+            }
+        }
+    }
 }
 impl Expression {
-    pub fn eval(&self,  variables: &mut HashMap<String, RuntimeValue>) -> Result<RuntimeValue> {
+    pub fn eval(&self,  variables: &mut Environment) -> Result<RuntimeValue> {
         match self {
             Expression::Binary(left, Operator::Add, right) => left.eval(variables)? + right.eval(variables)?,
-            Expression::Binary(left, Operator::EqualEqual, right) => {
-                Ok(RuntimeValue::Boolean(left.eval(variables)? == right.eval(variables)?))
-            }
+            Expression::Binary(left, Operator::EqualEqual, right) => Ok(RuntimeValue::Boolean(
+                left.eval(variables)?.is_equal(&right.eval(variables)?),
+            )),
             Expression::Binary(left, Operator::Multiply, right) => {
                 left.eval(variables)?.multiply(right.eval(variables)?)
             }
@@ -275,9 +1190,9 @@ impl Expression {
                 left.eval(variables)?.subtract(right.eval(variables)?)
             }
             Expression::Binary(left, Operator::Power, right) => todo!(),
-            Expression::Binary(left, Operator::BangEqual, right) => {
-                Ok(RuntimeValue::Boolean(left.eval(variables)? != right.eval(variables)?))
-            }
+            Expression::Binary(left, Operator::BangEqual, right) => Ok(RuntimeValue::Boolean(
+                !left.eval(variables)?.is_equal(&right.eval(variables)?),
+            )),
             Expression::Binary(left, Operator::LessEqual, right) => {
                 left.eval(variables)?.less_than_equal(right.eval(variables)?)
             }
@@ -291,22 +1206,101 @@ impl Expression {
                 left.eval(variables)?.greater_equal(right.eval(variables)?)
             }
             Expression::Binary(left, Operator::And, right) => left.eval(variables)?.and(right.eval(variables)?),
-            Expression::Binary(left, Operator::Or, right) => todo!(),
+            Expression::Binary(left, Operator::Or, right) => match left.eval(variables)? {
+                value if value.is_truthy() => Ok(value),
+                _ => right.eval(variables),
+            },
             Expression::Unary(Operator::Subtract, expr) => expr.eval(variables)?.negate(),
             Expression::Unary(Operator::Bang, expr) => expr.eval(variables)?.not_and(),
-            Expression::Number(val) => Ok(RuntimeValue::Number(*val)),
+            Expression::Number(val) => Ok(RuntimeValue::literal(*val)),
+            Expression::Float(val) => Ok(RuntimeValue::Number(*val)),
+            Expression::Integer(val) => Ok(RuntimeValue::from_big(val.clone())),
             Expression::Boolean(val) => Ok(RuntimeValue::Boolean(*val)),
             Expression::String(val) => Ok(RuntimeValue::String(val.into())),
+            Expression::Bytes(val) => Ok(RuntimeValue::Bytes(val.clone())),
             Expression::Group(expr) => expr.eval(variables),
             Expression::Nil => Ok(RuntimeValue::Nil),
             Expression::Identifier(ident) => {
-                let val =variables.get(ident).context(format!("Undefined varialbe '{ident}'."))?.clone();
+                let val =variables.get(ident).context(format!("Undefined variable '{ident}'."))?.clone();
                 Ok(val)
                 
             }
+            Expression::Binary(left, Operator::Coalesce, right) => match left.eval(variables)? {
+                RuntimeValue::Nil => right.eval(variables),
+                value => Ok(value),
+            },
+            Expression::Range(start, end, inclusive) => {
+                let (start, end) = (start.eval(variables)?, end.eval(variables)?);
+                match (start.as_float(), end.as_float()) {
+                    (Some(start), Some(end)) => {
+                        Ok(RuntimeValue::Range(start, end, 1.0, *inclusive))
+                    }
+                    _ => Err(anyhow!(
+                        "Range bounds must be numbers, got {} and {}.",
+                        start.type_name(),
+                        end.type_name()
+                    )),
+                }
+            }
+            Expression::Tuple(items) => Ok(RuntimeValue::Tuple(
+                items
+                    .iter()
+                    .map(|item| item.eval(variables))
+                    .collect::<Result<_>>()?,
+            )),
+            Expression::Pipeline(value, f) => {
+                let value = value.eval(variables)?;
+                let (callee, args) = match f.as_ref() {
+                    Expression::Call(callee, args, _) => (callee.as_ref(), args.as_slice()),
+                    callee => (callee, [].as_slice()),
+                };
+                let Some(callee) = callee.eval_chain(variables)? else {
+                    return Ok(RuntimeValue::Nil);
+                };
+                let args = iter::once((None, value))
+                    .chain(eval_arguments(args, variables)?)
+                    .collect();
+                call(callee, args)
+            }
+            Expression::Get(_, _)
+            | Expression::Call(_, _, _)
+            | Expression::Index(_, _)
+            | Expression::Optional(_) => {
+                Ok(self.eval_chain(variables)?.unwrap_or(RuntimeValue::Nil))
+            }
+            Expression::Assign(name, value) => {
+                let value = value.eval(variables)?;
+                assign(variables, name, value.clone())?;
+                Ok(value)
+            }
             _ => todo!(),
         }
     }
+    /// Evaluates a chain of property accesses, calls and indexing, returning
+    /// `None` when a `?.` receiver was nil so the rest of the chain is skipped.
+    fn eval_chain(&self, variables: &mut Environment) -> Result<Option<RuntimeValue>> {
+        match self {
+            Expression::Optional(receiver) => Ok(receiver
+                .eval_chain(variables)?
+                .filter(|value| !matches!(value, RuntimeValue::Nil))),
+            Expression::Get(object, name) => match object.eval_chain(variables)? {
+                Some(object) => get_property(object, name).map(Some),
+                None => Ok(None),
+            },
+            Expression::Index(object, index) => match object.eval_chain(variables)? {
+                Some(object) => get_index(object, index.eval(variables)?).map(Some),
+                None => Ok(None),
+            },
+            Expression::Call(callee, args, _) => {
+                let Some(callee) = callee.eval_chain(variables)? else {
+                    return Ok(None);
+                };
+                let args = eval_arguments(args, variables)?;
+                call(callee, args).map(Some)
+            }
+            _ => self.eval(variables).map(Some),
+        }
+    }
 }
 
 #[test]
@@ -314,8 +1308,8 @@ fn test_add() {
     let tokenizer = Tokenizer::new("1+2+3+4".into());
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
-    let mut varialbes = HashMap::new();
-    let p = parser.parse().unwrap().eval(&mut varialbes).unwrap();
+    let mut variables = Environment::new();
+    let p = parser.parse().unwrap().eval(&mut variables).unwrap();
 
     println!("{:?}", p);
     assert_eq!(p, RuntimeValue::Number(10.0));
@@ -325,8 +1319,8 @@ fn test_add_strings() {
     let tokenizer = Tokenizer::new("\"Hello \" + \"World\"".into());
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
-    let mut varialbes = HashMap::new();
-    let p = parser.parse().unwrap().eval(&mut varialbes).unwrap();
+    let mut variables = Environment::new();
+    let p = parser.parse().unwrap().eval(&mut variables).unwrap();
 
     println!("{:?}", p);
     assert_eq!(p, RuntimeValue::String("Hello World".into()));
@@ -337,8 +1331,8 @@ fn test_bool_logic() {
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
     //println!("{:?}", parser.parse().unwrap());
-    let mut varialbes = HashMap::new();
-    let p = parser.parse().unwrap().eval(&mut varialbes).unwrap();
+    let mut variables = Environment::new();
+    let p = parser.parse().unwrap().eval(&mut variables).unwrap();
 
     println!("{:?}", p);
     assert_eq!(p, RuntimeValue::Boolean(true));
@@ -349,8 +1343,8 @@ fn test_bool_nil() {
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
     //println!("{:?}", parser.parse().unwrap());
-    let mut varialbes = HashMap::new();
-    let p = parser.parse().unwrap().eval(&mut varialbes).unwrap();
+    let mut variables = Environment::new();
+    let p = parser.parse().unwrap().eval(&mut variables).unwrap();
 
     println!("{:?}", p);
     assert_eq!(p, RuntimeValue::Nil);
@@ -362,8 +1356,8 @@ fn test_bool_nil_asd() {
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
     //println!("{:?}", parser.parse().unwrap());
-    let mut varialbes = HashMap::new();
-    let p = parser.parse().unwrap().eval(&mut varialbes).unwrap();
+    let mut variables = Environment::new();
+    let p = parser.parse().unwrap().eval(&mut variables).unwrap();
 
     println!("{:?}", p);
     assert_eq!(p, RuntimeValue::Nil);
@@ -374,9 +1368,9 @@ fn test_long_math_expr() {
     let tokenizer = Tokenizer::new("((2+5)/3 * (1+2+7)/2) / 0.25 * (1/2 + 2/3 + 4/5) + ((3/4 + 4/5) * 10) / 2 + 0.0723 + 0.60002222222222".into());
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
-    let mut varialbes = HashMap::new();
+    let mut variables = Environment::new();
     // println!("{:?}", parser.parse().unwrap());
-    let p = parser.parse().unwrap().eval(&mut varialbes).unwrap();
+    let p = parser.parse().unwrap().eval(&mut variables).unwrap();
 
     println!("{:?}", p);
     assert_eq!(p, RuntimeValue::Number(100.2001));
@@ -385,13 +1379,13 @@ fn test_long_math_expr() {
 #[test]
 fn test_overflow() {
     //(46 + 85 - 94) > (54 - 46) * 2;
-    let mut varialbes = HashMap::new();
+    let mut variables = Environment::new();
     let tokenizer = Tokenizer::new("(46 + 85 - 94) > (54 - 46) * 2;".into());
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
     // println!("{:?}", parser.parse().unwrap());
  
-    let p = parser.parse_program().unwrap().eval(&mut varialbes).unwrap();
+    let p = parser.parse_program().unwrap().eval(&mut variables).unwrap();
 
     println!("{:?}", p);
 }
@@ -404,8 +1398,1243 @@ fn test_var_decl() {
     let mut iter = tokenizer.iter().peekable();
     let mut parser = Parser::new(&mut iter);
      println!("{:?}", parser.parse_program().unwrap());
-  //  let mut varialbes = HashMap::new();
-  //  let p = parser.parse_program().unwrap().eval(&mut varialbes).unwrap();
+  //  let mut variables = HashMap::new();
+  //  let p = parser.parse_program().unwrap().eval(&mut variables).unwrap();
 
    // println!("{:?}", p);
 }
+
+#[test]
+fn test_match_eval() {
+    let tokenizer = Tokenizer::new(
+        "var r = nil; match (x) { 1 | 2 => r = \"small\"; 3..10 if x != 5 => r = \"mid\"; \"a\" => r = \"str\"; _ => r = \"other\"; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let program = parser.parse_program().unwrap();
+
+    for (x, expected) in [
+        (RuntimeValue::Number(2.0), "small"),
+        (RuntimeValue::Number(7.0), "mid"),
+        (RuntimeValue::Number(5.0), "other"),
+        (RuntimeValue::Number(10.0), "other"),
+        (RuntimeValue::String("a".into()), "str"),
+        (RuntimeValue::Nil, "other"),
+    ] {
+        let mut variables = Environment::new();
+        variables.insert("x".to_string(), x);
+        program.eval(&mut variables).unwrap();
+        assert_eq!(variables["r"], RuntimeValue::String(expected.into()));
+    }
+}
+
+#[test]
+fn test_match_error_line() {
+    let tokenizer = Tokenizer::new("var x = 1;\nmatch (x) { 1 if y => print x; }".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "Undefined variable 'y'.\n[line 2]");
+}
+
+#[test]
+fn test_contextual_keywords() {
+    let tokenizer = Tokenizer::new(
+        "var match = 1;
+var try = match + 1;
+var enum = nil;
+match (try) { 2 => enum = \"two\"; }
+try { throw enum; } catch (catch) { match = catch; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["try"], RuntimeValue::Number(2.0));
+    assert_eq!(variables["match"], RuntimeValue::String("two".into()));
+}
+
+#[test]
+fn test_catch_runtime_error() {
+    let tokenizer = Tokenizer::new(
+        "var line = nil;
+try {
+  1 + \"a\";
+} catch (e) {
+  line = e.line;
+}"
+        .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["line"], RuntimeValue::Number(3.0));
+}
+
+#[test]
+fn test_catch_thrown_value() {
+    let tokenizer = Tokenizer::new(
+        "var caught = nil;
+try { throw \"boom\"; } catch (e) { caught = e; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["caught"], RuntimeValue::String("boom".into()));
+}
+
+#[test]
+fn test_finally_after_catch() {
+    let tokenizer = Tokenizer::new(
+        "var done = false;
+try { nil.x; } catch (e) {} finally { done = true; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["done"], RuntimeValue::Boolean(true));
+}
+
+#[test]
+fn test_try_catch_scope() {
+    let tokenizer = Tokenizer::new("try { var x = 1; throw x; } catch (e) { var y = e; }".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert!(variables.get("x").is_none());
+    assert!(variables.get("e").is_none());
+    assert!(variables.get("y").is_none());
+}
+
+#[test]
+fn test_uncaught_error_line() {
+    let tokenizer = Tokenizer::new("var a = 1;\ntry { print b; } finally { a = 2; }".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "Undefined variable 'b'.\n[line 2]");
+    assert_eq!(variables["a"], RuntimeValue::Number(2.0));
+}
+
+#[test]
+fn test_const_runtime_assignment() {
+    let tokenizer = Tokenizer::new("var b = a + 1;\na = 2;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    variables.insert_const("a".to_string(), RuntimeValue::Number(1.0));
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(variables["b"], RuntimeValue::Number(2.0));
+    assert_eq!(err.to_string(), "Cannot assign to constant 'a'.\n[line 2]");
+}
+
+#[test]
+fn test_const_after_shadowing() {
+    let mut variables = Environment::new();
+    let eval = |source: &str, variables: &mut Environment| {
+        let tokenizer = Tokenizer::new(source.into());
+        let mut iter = tokenizer.iter().peekable();
+        let mut parser = Parser::new(&mut iter);
+        parser.parse_program().unwrap().eval(variables)
+    };
+    eval("const a = 1;\n{ var a = 2; a = 3; }", &mut variables).unwrap();
+    let err = eval("a = 4;", &mut variables).unwrap_err();
+
+    assert_eq!(err.to_string(), "Cannot assign to constant 'a'.\n[line 1]");
+    assert_eq!(variables["a"], RuntimeValue::Number(1.0));
+}
+
+#[test]
+fn test_for_in() {
+    let tokenizer = Tokenizer::new(
+        "var reversed = \"\";
+for (var c in \"abc\") reversed = c + reversed;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["reversed"], RuntimeValue::String("cba".into()));
+}
+
+#[test]
+fn test_for_in_shadowing() {
+    let tokenizer = Tokenizer::new(
+        "var c = \"kept\";
+for (var c in \"abc\") {}"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["c"], RuntimeValue::String("kept".into()));
+}
+
+#[test]
+fn test_for_in_not_iterable() {
+    let tokenizer = Tokenizer::new("for (c in 12) {}".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Value of type Number is not iterable.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_destructure_nested_rest() {
+    let tokenizer = Tokenizer::new("var [first, [second], ...rest] = \"lox!\";".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["first"], RuntimeValue::String("l".into()));
+    assert_eq!(variables["second"], RuntimeValue::String("o".into()));
+    assert_eq!(variables["rest"], RuntimeValue::String("x!".into()));
+}
+
+#[test]
+fn test_destructure_assign() {
+    let tokenizer = Tokenizer::new(
+        "var a = nil;
+var b = nil;
+[a, b] = \"xy\";"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["a"], RuntimeValue::String("x".into()));
+    assert_eq!(variables["b"], RuntimeValue::String("y".into()));
+}
+
+#[test]
+fn test_destructure_fields() {
+    let tokenizer = Tokenizer::new(
+        "var message = nil;
+var where = nil;
+try { 1 - true; } catch (e) { var {message: m, line} = e; message = m; where = line; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(
+        variables["message"],
+        RuntimeValue::String("Cannot multiply f64:1 with bool:true".into())
+    );
+    assert_eq!(variables["where"], RuntimeValue::Number(3.0));
+    assert!(variables.get("m").is_none());
+}
+
+#[test]
+fn test_destructure_length_mismatch() {
+    let tokenizer = Tokenizer::new("var [p, q, r] = \"ab\";".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Expected 3 elements to destructure but got 2.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_string_length() {
+    let tokenizer = Tokenizer::new("var length = \" Grüße, Welt \".length;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["length"], RuntimeValue::Number(13.0));
+}
+
+#[test]
+fn test_string_case() {
+    let tokenizer = Tokenizer::new(
+        "var upper = \" Grüße, Welt \".trim().upper();
+var lower = \"LoX\".lower();"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(
+        variables["upper"],
+        RuntimeValue::String("GRÜSSE, WELT".into())
+    );
+    assert_eq!(variables["lower"], RuntimeValue::String("lox".into()));
+}
+
+#[test]
+fn test_string_search() {
+    let tokenizer = Tokenizer::new(
+        "var s = \" Grüße, Welt \";
+var index = s.indexOf(\"e\");
+var replaced = s.replace(\"Welt\", \"Lox\").contains(\"Lox\");"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["index"], RuntimeValue::Number(5.0));
+    assert_eq!(variables["replaced"], RuntimeValue::Boolean(true));
+}
+
+#[test]
+fn test_string_slice() {
+    let tokenizer = Tokenizer::new("var slice = \" Grüße, Welt \".slice(1, -7);".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["slice"], RuntimeValue::String("Grüße".into()));
+}
+
+#[test]
+fn test_string_split() {
+    let tokenizer = Tokenizer::new("var parts = \"a-b-c\".split(\"-\");".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(
+        variables["parts"],
+        RuntimeValue::List(vec![
+            RuntimeValue::String("a".into()),
+            RuntimeValue::String("b".into()),
+            RuntimeValue::String("c".into())
+        ])
+    );
+}
+
+#[test]
+fn test_string_slice_index_type() {
+    let tokenizer = Tokenizer::new("\"lox\".slice(\"1\", 2);".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Index must be an integer, got 1.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_optional_chaining() {
+    let tokenizer = Tokenizer::new(
+        "var missing = nil;
+var chained = missing?.trim().upper().length;
+var indexed = missing?.[0];
+var present = \"lox\"?.[2];"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["chained"], RuntimeValue::Nil);
+    assert_eq!(variables["indexed"], RuntimeValue::Nil);
+    assert_eq!(variables["present"], RuntimeValue::String("x".into()));
+}
+
+#[test]
+fn test_coalesce() {
+    let tokenizer = Tokenizer::new(
+        "var fallback = nil ?? \"lox\".upper();
+var kept = false ?? true;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["fallback"], RuntimeValue::String("LOX".into()));
+    assert_eq!(variables["kept"], RuntimeValue::Boolean(false));
+}
+
+#[test]
+fn test_coalesce_with_or() {
+    let tokenizer = Tokenizer::new(
+        "var first = nil ?? 1 or 2;
+var second = nil ?? false or \"right\";
+var skipped = true or missing;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["first"], RuntimeValue::Number(1.0));
+    assert_eq!(variables["second"], RuntimeValue::String("right".into()));
+    assert_eq!(variables["skipped"], RuntimeValue::Boolean(true));
+}
+
+#[test]
+fn test_index_call_result() {
+    let tokenizer = Tokenizer::new("var second = \"lox\".split(\"o\")[1];".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["second"], RuntimeValue::String("x".into()));
+}
+
+#[test]
+fn test_index_out_of_range() {
+    let tokenizer = Tokenizer::new("\"lox\"[3];".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Index 3 is out of range for length 3.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_pipeline_into_call() {
+    let tokenizer = Tokenizer::new(
+        "var parts = \";\" |> \" a;B \".trim().lower().split();
+var found = \"b\" |> \"abc\".contains();"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(
+        variables["parts"],
+        RuntimeValue::List(vec![
+            RuntimeValue::String("a".into()),
+            RuntimeValue::String("b".into())
+        ])
+    );
+    assert_eq!(variables["found"], RuntimeValue::Boolean(true));
+}
+
+#[test]
+fn test_pipeline_into_method() {
+    let tokenizer = Tokenizer::new("var index = \"c\" |> \"abc\".indexOf;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["index"], RuntimeValue::Number(2.0));
+}
+
+#[test]
+fn test_pipeline_into_nil() {
+    let tokenizer = Tokenizer::new("var skipped = \"x\" |> nil?.contains;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["skipped"], RuntimeValue::Nil);
+}
+
+#[test]
+fn test_pipeline_into_value() {
+    let tokenizer = Tokenizer::new("\"x\" |> \"lox\".length;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Can only call functions and classes.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_range_iteration() {
+    let tokenizer = Tokenizer::new(
+        "var sum = 0;
+for (var i in 1..=4) sum = sum + i;
+for (var i in 1..4) sum = sum + i;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["sum"], RuntimeValue::Number(16.0));
+}
+
+#[test]
+fn test_range_step() {
+    let tokenizer = Tokenizer::new(
+        "var evens = \"\";
+for (var i in (0..10).step(4)) evens = evens + \"-\";
+var range = (0..3).step(0.5);"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["evens"], RuntimeValue::String("---".into()));
+    assert_eq!(variables["range"].to_string(), "(0..3).step(0.5)");
+}
+
+#[test]
+fn test_range_slices() {
+    let tokenizer = Tokenizer::new(
+        "var word = \"grüße\";
+var middle = word[1..4];
+var tail = word[2..=-1];
+var skipped = word[(0..5).step(2)];
+var letters = \"a b c d\".split(\" \")[1..3];"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["middle"], RuntimeValue::String("rüß".into()));
+    assert_eq!(variables["tail"], RuntimeValue::String("üße".into()));
+    assert_eq!(variables["skipped"], RuntimeValue::String("güe".into()));
+    assert_eq!(
+        variables["letters"],
+        RuntimeValue::List(vec![
+            RuntimeValue::String("b".into()),
+            RuntimeValue::String("c".into())
+        ])
+    );
+}
+
+#[test]
+fn test_range_negative_step() {
+    let tokenizer = Tokenizer::new("(0..3).step(-1);".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Range step must be a positive number, got -1.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_tuple_fields() {
+    let tokenizer = Tokenizer::new(
+        "var pair = (7, (\"x\", nil));
+var first = pair.0;
+var nested = pair.1.0;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["first"], RuntimeValue::Number(7.0));
+    assert_eq!(variables["nested"], RuntimeValue::String("x".into()));
+}
+
+#[test]
+fn test_tuple_equality() {
+    let tokenizer = Tokenizer::new(
+        "var pair = (7, (\"x\", nil));
+var same = pair == (7, (\"x\", nil));
+var different = pair == (7, \"x\", nil);"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["same"], RuntimeValue::Boolean(true));
+    assert_eq!(variables["different"], RuntimeValue::Boolean(false));
+}
+
+#[test]
+fn test_tuple_destructuring() {
+    let tokenizer = Tokenizer::new("var (q, (name, _)) = (7, (\"x\", nil));".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["q"], RuntimeValue::Number(7.0));
+    assert_eq!(variables["name"], RuntimeValue::String("x".into()));
+}
+
+#[test]
+fn test_tuple_display() {
+    let tokenizer = Tokenizer::new(
+        "var single = (1,);
+var pair = (7, (\"x\", nil));"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["single"].to_string(), "(1,)");
+    assert_eq!(variables["pair"].to_string(), "(7, (x, nil))");
+}
+
+#[test]
+fn test_tuple_index_out_of_range() {
+    let tokenizer = Tokenizer::new("(1, 2).2;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Tuple index 2 is out of range for length 2.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_struct_construction() {
+    let tokenizer = Tokenizer::new(
+        "struct Point { x, y }
+var p = Point(1, 2);
+var x = p.x;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["p"].to_string(), "Point(x: 1, y: 2)");
+    assert_eq!(variables["x"], RuntimeValue::Number(1.0));
+}
+
+#[test]
+fn test_struct_with() {
+    let tokenizer = Tokenizer::new(
+        "struct Point { x, y }
+var p = Point(1, 2);
+var moved = p.with(y: 5);"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["moved"].to_string(), "Point(x: 1, y: 5)");
+    assert_eq!(variables["p"].to_string(), "Point(x: 1, y: 2)");
+}
+
+#[test]
+fn test_struct_equality() {
+    let tokenizer = Tokenizer::new(
+        "struct Point { x, y }
+var same = Point(y: 2, x: 1) == Point(1, 2);
+var different = Point(1, 5) == Point(1, 2);"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["same"], RuntimeValue::Boolean(true));
+    assert_eq!(variables["different"], RuntimeValue::Boolean(false));
+}
+
+#[test]
+fn test_struct_with_positional() {
+    let tokenizer = Tokenizer::new(
+        "struct Point { x, y }
+Point(1, 2).with(3);"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Arguments to 'with' must be named.
+[line 2]"
+    );
+}
+
+#[test]
+fn test_integer_promotion() {
+    let tokenizer = Tokenizer::new(
+        "var factorial = 1;
+for (i in 1..=25) { factorial = factorial * i; }
+var exact = 9007199254740992 + 1;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(
+        variables["factorial"].to_string(),
+        "15511210043330985984000000"
+    );
+    assert_eq!(variables["exact"].to_string(), "9007199254740993");
+}
+
+#[test]
+fn test_integer_demotion() {
+    let tokenizer = Tokenizer::new("var back = 9223372036854775807 + 1 - 1;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["back"], RuntimeValue::Integer(i64::MAX));
+}
+
+#[test]
+fn test_integer_division() {
+    let tokenizer = Tokenizer::new("var half = 7 / 2;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["half"], RuntimeValue::Number(3.5));
+}
+
+#[test]
+fn test_integer_float_mix() {
+    let tokenizer = Tokenizer::new(
+        "var mixed = 1.5 + 1;
+var same = 1 == 1.0;
+var bigger = 15511210043330985984000000 > 9007199254740992;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["mixed"], RuntimeValue::Number(2.5));
+    assert_eq!(variables["same"], RuntimeValue::Boolean(true));
+    assert_eq!(variables["bigger"], RuntimeValue::Boolean(true));
+}
+
+#[test]
+fn test_integer_literal_precision() {
+    let tokenizer = Tokenizer::new(
+        "var above = 9007199254740993;
+var equal = 9007199254740993 == 9007199254740992;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["above"].to_string(), "9007199254740993");
+    assert_eq!(variables["equal"], RuntimeValue::Boolean(false));
+}
+
+#[test]
+fn test_whole_float_literal() {
+    let tokenizer = Tokenizer::new("var whole = 1.0;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert!(matches!(variables["whole"], RuntimeValue::Number(_)));
+}
+
+#[test]
+fn test_bytes_index_slice() {
+    let tokenizer = Tokenizer::new(
+        "var data = b\"Lox\\x00\\xff\";
+var first = data[0];
+var tail = data[1..5];"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["first"], RuntimeValue::Integer(76));
+    assert_eq!(variables["tail"].to_string(), "b\"ox\\x00\\xff\"");
+}
+
+#[test]
+fn test_bytes_concat() {
+    let tokenizer = Tokenizer::new("var joined = b\"Lox\" + b\"!\";".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["joined"].to_string(), "b\"Lox!\"");
+}
+
+#[test]
+fn test_bytes_text() {
+    let tokenizer = Tokenizer::new("var decoded = \"héllo\".toBytes().toString();".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["decoded"], RuntimeValue::String("héllo".into()));
+}
+
+#[test]
+fn test_bytes_encodings() {
+    let tokenizer = Tokenizer::new(
+        "var hex = b\"Lox\\x00\\xff\".toHex();
+var base64 = \"héllo\".toBytes().toBase64();
+var same = \"4c6f78\".decodeHex() == b\"Lox\";"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["hex"], RuntimeValue::String("4c6f7800ff".into()));
+    assert_eq!(variables["base64"], RuntimeValue::String("aMOpbGxv".into()));
+    assert_eq!(variables["same"], RuntimeValue::Boolean(true));
+}
+
+#[test]
+fn test_bytes_invalid_utf8() {
+    let tokenizer = Tokenizer::new("b\"\\xff\".toString();".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Bytes are not valid UTF-8.
+[line 1]"
+    );
+}
+
+#[test]
+fn test_infix_operators() {
+    let tokenizer = Tokenizer::new(
+        "struct Pair { a, b }
+infix <+> 60 left = Pair;
+var p = 1 <+> 2 * 3;
+var nested = 1 <+> 2 <+> 3;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["p"].to_string(), "Pair(a: 1, b: 6)");
+    assert_eq!(
+        variables["nested"].to_string(),
+        "Pair(a: Pair(a: 1, b: 2), b: 3)"
+    );
+}
+
+#[test]
+fn test_enum_variants() {
+    let tokenizer = Tokenizer::new(
+        "enum Shape { Circle(r), Rect(w, h) }
+var circle = Shape.Circle(2);
+var rect = Shape.Rect(h: 4, w: 3);"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["circle"].to_string(), "Shape.Circle(r: 2)");
+    assert_eq!(variables["rect"].to_string(), "Shape.Rect(w: 3, h: 4)");
+}
+
+#[test]
+fn test_enum_equality() {
+    let tokenizer = Tokenizer::new(
+        "enum Shape { Circle(r), Empty }
+var same = Shape.Circle(2) == Shape.Circle(2);
+var different = Shape.Circle(3) == Shape.Circle(2);
+var empty = Shape.Empty == Shape.Empty;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["same"], RuntimeValue::Boolean(true));
+    assert_eq!(variables["different"], RuntimeValue::Boolean(false));
+    assert_eq!(variables["empty"], RuntimeValue::Boolean(true));
+}
+
+#[test]
+fn test_enum_match() {
+    let tokenizer = Tokenizer::new(
+        "enum Shape { Circle(r), Rect(w, h), Empty }
+var areas = 0;
+for (shape in (Shape.Circle(2), Shape.Rect(3, 4), Shape.Empty, Shape.Rect(10, 10))) {
+    match (shape) {
+        Shape.Circle(r) => areas = areas + 3 * r * r;
+        Shape.Rect(w, h) if w < 5 => areas = areas + w * h;
+        Shape.Rect => areas = areas + 1;
+        Shape.Empty => areas = areas + 1000;
+    }
+}"
+        .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["areas"], RuntimeValue::Number(1025.0));
+}
+
+#[test]
+fn test_enum_match_scope() {
+    let tokenizer = Tokenizer::new(
+        "enum Shape { Rect(w, h) }
+var w = \"outer\";
+match (Shape.Rect(3, 4)) { Shape.Rect(w, h) => print w; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["w"], RuntimeValue::String("outer".into()));
+    assert!(variables.get("h").is_none());
+}
+
+#[test]
+fn test_enum_pattern_arity() {
+    let tokenizer = Tokenizer::new(
+        "enum Shape { Rect(w, h) }
+var rect = Shape.Rect(3, 4);
+match (rect) { Shape.Rect(w) => print w; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    let err = parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Expected 2 fields for Shape.Rect but got 1.\n[line 3]"
+    );
+}
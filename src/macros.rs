@@ -0,0 +1,574 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::token::Token;
+
+use anyhow::{anyhow, Ok, Result};
+
+// Expansions that keep producing further invocations are assumed to recurse forever
+const MAX_DEPTH: usize = 64;
+
+/// What a `$name:kind` fragment in a macro pattern accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fragment {
+    // Tokens up to whatever the pattern expects next
+    Expr,
+    // A `{ ... }` block
+    Block,
+    Ident,
+    // A single token, or a bracketed group of them
+    Tt,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    // A token the invocation has to spell out exactly
+    Literal(String),
+    Fragment(String, Fragment),
+}
+
+#[derive(Debug)]
+struct Macro {
+    pattern: Vec<Matcher>,
+    template: Vec<Token>,
+}
+
+#[derive(Default)]
+struct Expander {
+    macros: HashMap<String, Macro>,
+    // Counts expansions, to give the names each one binds a fresh suffix
+    expansions: usize,
+}
+
+/// Removes `macro` definitions from a token stream and replaces each
+/// invocation with its expansion.
+///
+/// A definition reads `macro name pattern => { template }`. The pattern
+/// starts with `(` and mixes literal tokens with `$name:kind` fragments,
+/// where kind is `expr`, `block`, `ident` or `tt`, and the template refers
+/// back to them as `$name`. A name is only expanded where it is called, as
+/// in `name(...)`, so it stays usable as a variable or property name.
+/// Names the template declares itself are renamed apart on every
+/// expansion, so they can neither capture nor be captured by the caller's.
+/// The expansion takes the line of its invocation.
+pub fn expand(tokens: impl IntoIterator<Item = Token>) -> Result<Vec<Token>> {
+    Expander::default().expand(tokens.into_iter().collect(), 0)
+}
+
+impl Expander {
+    fn expand(&mut self, tokens: Vec<Token>, depth: usize) -> Result<Vec<Token>> {
+        let mut expanded = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            match (&tokens[i], tokens.get(i + 1)) {
+                (
+                    Token::Identifier(keyword, _, _, _),
+                    Some(Token::Identifier(name, line, _, _)),
+                ) if keyword == "macro" => {
+                    let (definition, end) = parse_definition(&tokens, i + 2, name, *line)?;
+                    self.macros.insert(name.clone(), definition);
+                    i = end;
+                }
+                (Token::Identifier(name, line, _, _), _)
+                    if self.macros.contains_key(name) && is_invocation(&tokens, i) =>
+                {
+                    if depth == MAX_DEPTH {
+                        return Err(anyhow!(
+                            "[line {line}] Error at '{name}': Macro expansion is too deeply nested."
+                        ));
+                    }
+                    let (bindings, end) = match_pattern(&self.macros[name], &tokens, i + 1)
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "[line {line}] Error at '{name}': Invocation does not match the macro's pattern."
+                            )
+                        })?;
+                    let instance = self.instantiate(name, &bindings, *line);
+                    // A template of whole statements already ends in its own
+                    // semicolon, so the one after the invocation is dropped
+                    let statements = matches!(instance.last(), Some(Token::Semicolon(_, _, _)));
+                    expanded.extend(self.expand(instance, depth + 1)?);
+                    i = end;
+                    if statements && matches!(tokens.get(i), Some(Token::Semicolon(_, _, _))) {
+                        i += 1;
+                    }
+                }
+                (token, _) => {
+                    expanded.push(token.clone());
+                    i += 1;
+                }
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn instantiate(
+        &mut self,
+        name: &str,
+        bindings: &HashMap<String, Vec<Token>>,
+        line: u32,
+    ) -> Vec<Token> {
+        self.expansions += 1;
+        let template = &self.macros[name].template;
+        let bound = bound_names(template);
+        let mut instance = Vec::new();
+        let mut tokens = template.iter();
+        let mut previous: Option<&Token> = None;
+        while let Some(token) = tokens.next() {
+            // Property names after `.` are not variables
+            let property = matches!(
+                previous,
+                Some(Token::Dot(_, _, _) | Token::QuestionDot(_, _, _))
+            );
+            previous = Some(token);
+            match token {
+                Token::Dollar(_, _, _) => {
+                    if let Some(Token::Identifier(fragment, _, _, _)) = tokens.next() {
+                        instance.extend(bindings[fragment].iter().cloned());
+                    }
+                }
+                Token::Identifier(name, _, col, _) if bound.contains(name) && !property => {
+                    let renamed = format!("{}#{}", name, self.expansions);
+                    instance.push(Token::Identifier(renamed.clone(), line, *col, renamed));
+                }
+                token => {
+                    let mut token = token.clone();
+                    token.set_line(line);
+                    instance.push(token);
+                }
+            }
+        }
+        instance
+    }
+}
+
+/// Reads a definition from just after its name, returning the macro and
+/// the position after its closing brace.
+fn parse_definition(
+    tokens: &[Token],
+    start: usize,
+    name: &str,
+    line: u32,
+) -> Result<(Macro, usize)> {
+    let error = |message: &str| anyhow!("[line {line}] Error at '{name}': {message}");
+    if !matches!(tokens.get(start), Some(Token::LeftParen(_, _, _))) {
+        return Err(error("Expected '(' to start macro pattern."));
+    }
+    let mut pattern = Vec::new();
+    let mut i = start;
+    loop {
+        match (
+            tokens.get(i),
+            tokens.get(i + 1),
+            tokens.get(i + 2),
+            tokens.get(i + 3),
+        ) {
+            (Some(Token::FatArrow(_, _, _)), _, _, _) => break,
+            (
+                Some(Token::Dollar(_, _, _)),
+                Some(Token::Identifier(fragment, _, _, _)),
+                Some(Token::Colon(_, _, _)),
+                Some(Token::Identifier(kind, _, _, _)),
+            ) => {
+                let kind = match kind.as_str() {
+                    "expr" => Fragment::Expr,
+                    "block" => Fragment::Block,
+                    "ident" => Fragment::Ident,
+                    "tt" => Fragment::Tt,
+                    _ => return Err(error(&format!("Unknown fragment kind '{kind}'."))),
+                };
+                if pattern.iter().any(
+                    |matcher| matches!(matcher, Matcher::Fragment(other, _) if other == fragment),
+                ) {
+                    return Err(error(&format!("Duplicate fragment '${fragment}'.")));
+                }
+                pattern.push(Matcher::Fragment(fragment.clone(), kind));
+                i += 4;
+            }
+            (Some(Token::Dollar(_, _, _)), _, _, _) => {
+                return Err(error("Expected '$name:kind' in macro pattern."))
+            }
+            (Some(token), _, _, _) => {
+                let (lexeme, _, _) = token
+                    .span()
+                    .ok_or_else(|| error("Unexpected token in macro pattern."))?;
+                pattern.push(Matcher::Literal(lexeme.to_owned()));
+                i += 1;
+            }
+            (None, _, _, _) => return Err(error("Expected '=>' after macro pattern.")),
+        }
+    }
+    for (matcher, next) in pattern.iter().zip(pattern.iter().skip(1)) {
+        if let (Matcher::Fragment(fragment, Fragment::Expr), Matcher::Fragment(_, kind)) =
+            (matcher, next)
+        {
+            if *kind != Fragment::Block {
+                return Err(error(&format!(
+                    "Fragment '${fragment}' must be followed by a literal token or a block."
+                )));
+            }
+        }
+    }
+
+    if !matches!(tokens.get(i + 1), Some(Token::LeftBrace(_, _, _))) {
+        return Err(error("Expected '{' before macro template."));
+    }
+    let end = tree_end(tokens, i + 1).ok_or_else(|| error("Unterminated macro template."))?;
+    let template = tokens[i + 2..end - 1].to_vec();
+    let mut template_tokens = template.iter();
+    while let Some(token) = template_tokens.next() {
+        if let Token::Dollar(_, _, _) = token {
+            let known = match template_tokens.next() {
+                Some(Token::Identifier(fragment, _, _, _)) => pattern.iter().any(
+                    |matcher| matches!(matcher, Matcher::Fragment(name, _) if name == fragment),
+                ),
+                _ => false,
+            };
+            if !known {
+                return Err(error(
+                    "Template refers to a fragment the pattern does not define.",
+                ));
+            }
+        }
+    }
+    Ok((Macro { pattern, template }, end))
+}
+
+/// Whether the macro name at `i` is called, rather than declared or read
+/// as a property.
+fn is_invocation(tokens: &[Token], i: usize) -> bool {
+    let declared = i.checked_sub(1).is_some_and(|previous| {
+        matches!(
+            tokens[previous],
+            Token::Dot(_, _, _)
+                | Token::QuestionDot(_, _, _)
+                | Token::Var(_, _, _)
+                | Token::Fun(_, _, _)
+                | Token::Class(_, _, _)
+        ) || ["const", "struct", "enum"]
+            .iter()
+            .any(|word| is_word(&tokens[previous], word))
+    });
+    !declared && matches!(tokens.get(i + 1), Some(Token::LeftParen(_, _, _)))
+}
+
+/// Matches an invocation starting at `start`, returning what each fragment
+/// captured and the position after the invocation.
+fn match_pattern(
+    definition: &Macro,
+    tokens: &[Token],
+    start: usize,
+) -> Option<(HashMap<String, Vec<Token>>, usize)> {
+    let mut bindings = HashMap::new();
+    let mut i = start;
+    for (n, matcher) in definition.pattern.iter().enumerate() {
+        let end = match matcher {
+            Matcher::Literal(lexeme) => {
+                let (text, _, _) = tokens.get(i)?.span()?;
+                (text == lexeme).then_some(i + 1)?
+            }
+            Matcher::Fragment(_, Fragment::Ident) => match tokens.get(i)? {
+                Token::Identifier(_, _, _, _) => i + 1,
+                _ => return None,
+            },
+            Matcher::Fragment(_, Fragment::Tt) => tree_end(tokens, i)?,
+            Matcher::Fragment(_, Fragment::Block) => match tokens.get(i)? {
+                Token::LeftBrace(_, _, _) => tree_end(tokens, i)?,
+                _ => return None,
+            },
+            Matcher::Fragment(_, Fragment::Expr) => {
+                let mut end = i;
+                while let Some(token) = tokens.get(end) {
+                    let stop = match definition.pattern.get(n + 1) {
+                        Some(Matcher::Literal(lexeme)) => {
+                            token.span().is_some_and(|(text, _, _)| text == lexeme)
+                        }
+                        Some(_) => matches!(token, Token::LeftBrace(_, _, _)),
+                        None => matches!(token, Token::Semicolon(_, _, _)),
+                    };
+                    if stop {
+                        break;
+                    }
+                    end = tree_end(tokens, end)?;
+                }
+                if end == i {
+                    return None;
+                }
+                end
+            }
+        };
+        if let Matcher::Fragment(name, kind) = matcher {
+            let mut captured = tokens[i..end].to_vec();
+            // Keep a captured expression together wherever the template puts it
+            if *kind == Fragment::Expr && captured.len() > 1 {
+                let (line, col) = tokens[i]
+                    .span()
+                    .map_or((0, 0), |(_, line, col)| (line, col));
+                captured.insert(0, Token::LeftParen("(".into(), line, col));
+                captured.push(Token::RightParen(")".into(), line, col));
+            }
+            bindings.insert(name.clone(), captured);
+        }
+        i = end;
+    }
+    Some((bindings, i))
+}
+
+/// The position after the token tree starting at `start`: a single token,
+/// or an opening bracket through its matching close.
+fn tree_end(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::LeftParen(_, _, _) | Token::LeftBrace(_, _, _) | Token::LeftBracket(_, _, _) => {
+                depth += 1
+            }
+            Token::RightParen(_, _, _)
+            | Token::RightBrace(_, _, _)
+            | Token::RightBracket(_, _, _) => {
+                if depth == 0 {
+                    return None;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        if depth == 0 {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// Names a template declares with `var`, `const`, `for` or `catch`.
+fn bound_names(template: &[Token]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for (i, token) in template.iter().enumerate() {
+        let name = match (token, template.get(i + 1), template.get(i + 2)) {
+            (Token::Var(_, _, _), Some(Token::Identifier(name, _, _, _)), _) => name,
+            (token, Some(Token::Identifier(name, _, _, _)), _) if is_word(token, "const") => name,
+            (
+                Token::For(_, _, _),
+                Some(Token::LeftParen(_, _, _)),
+                Some(Token::Identifier(name, _, _, _)),
+            ) => name,
+            // Unlike a call, a catch clause has its block right after
+            (token, Some(Token::LeftParen(_, _, _)), Some(Token::Identifier(name, _, _, _)))
+                if is_word(token, "catch")
+                    && matches!(template.get(i + 3), Some(Token::RightParen(_, _, _)))
+                    && matches!(template.get(i + 4), Some(Token::LeftBrace(_, _, _))) =>
+            {
+                name
+            }
+            _ => continue,
+        };
+        names.insert(name.clone());
+    }
+    names
+}
+
+/// Whether `token` is the identifier `word`, as the contextual keywords are.
+fn is_word(token: &Token, word: &str) -> bool {
+    matches!(token, Token::Identifier(name, _, _, _) if name == word)
+}
+
+/// Lays a token stream out as source text, one statement per line.
+pub fn render(tokens: &[Token]) -> String {
+    let spellings = spellings(tokens);
+    let mut source = String::new();
+    let mut indent = 0;
+    let mut line_start = true;
+    let mut previous: Option<&Token> = None;
+    for token in tokens {
+        let Some((lexeme, _, _)) = token.span() else {
+            continue;
+        };
+        let attaches = matches!(
+            token,
+            Token::Semicolon(_, _, _) | Token::Comma(_, _, _) | Token::RightParen(_, _, _)
+        );
+        if let Token::RightBrace(_, _, _) = token {
+            indent = usize::saturating_sub(indent, 1);
+            if !line_start {
+                source.push('\n');
+                line_start = true;
+            }
+        } else if line_start && attaches && matches!(previous, Some(Token::RightBrace(_, _, _))) {
+            // Keep `},` and `});` together instead of starting a line with them
+            source.pop();
+            line_start = false;
+        }
+        if line_start {
+            source.push_str(&"    ".repeat(indent));
+        } else if spaced(previous, token) {
+            source.push(' ');
+        }
+        source.push_str(spellings.get(lexeme).map_or(lexeme, String::as_str));
+        line_start = false;
+        match token {
+            Token::LeftBrace(_, _, _) => {
+                indent += 1;
+                source.push('\n');
+                line_start = true;
+            }
+            Token::RightBrace(_, _, _) | Token::Semicolon(_, _, _) => {
+                source.push('\n');
+                line_start = true;
+            }
+            _ => {}
+        }
+        previous = Some(token);
+    }
+    if !line_start {
+        source.push('\n');
+    }
+    source
+}
+
+/// Spells each hygienic `name#N` as an identifier nothing else in `tokens`
+/// uses, so rendered source parses back to the same program.
+fn spellings(tokens: &[Token]) -> HashMap<&str, String> {
+    let mut taken: HashSet<String> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Identifier(name, _, _, _) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut spellings = HashMap::new();
+    for token in tokens {
+        let Token::Identifier(renamed, _, _, _) = token else {
+            continue;
+        };
+        let Some((name, suffix)) = renamed.split_once('#') else {
+            continue;
+        };
+        if spellings.contains_key(renamed.as_str()) {
+            continue;
+        }
+        let mut spelling = format!("{name}_{suffix}");
+        while taken.contains(&spelling) {
+            spelling.push('_');
+        }
+        taken.insert(spelling.clone());
+        spellings.insert(renamed.as_str(), spelling);
+    }
+    spellings
+}
+
+fn spaced(previous: Option<&Token>, token: &Token) -> bool {
+    let glued_after = matches!(
+        previous,
+        Some(
+            Token::LeftParen(_, _, _)
+                | Token::LeftBracket(_, _, _)
+                | Token::Dot(_, _, _)
+                | Token::QuestionDot(_, _, _)
+                | Token::Bang(_, _, _)
+                | Token::Dollar(_, _, _)
+        )
+    );
+    let glued_before = match token {
+        Token::RightParen(_, _, _)
+        | Token::RightBracket(_, _, _)
+        | Token::Comma(_, _, _)
+        | Token::Semicolon(_, _, _)
+        | Token::Dot(_, _, _)
+        | Token::QuestionDot(_, _, _)
+        | Token::Colon(_, _, _) => true,
+        // Calls and indexing, as opposed to `for (` or `print (`
+        Token::LeftParen(_, _, _) | Token::LeftBracket(_, _, _) => matches!(
+            previous,
+            Some(
+                Token::Identifier(_, _, _, _)
+                    | Token::RightParen(_, _, _)
+                    | Token::RightBracket(_, _, _)
+            )
+        ),
+        _ => false,
+    };
+    !glued_after && !glued_before
+}
+
+#[test]
+fn test_macro_expansion() {
+    use crate::tokenizer::Tokenizer;
+
+    let expand_source = |source: &str| -> Result<String> {
+        let tokenizer = Tokenizer::new(source.to_string());
+        Ok(render(&expand(tokenizer.iter())?))
+    };
+    let source = "macro repeat($n:expr) $body:block => { for (i in 0..$n) $body }
+macro swap($a:ident, $b:ident) => { var tmp = $a; $a = $b; $b = tmp; }
+repeat(1 + 2) { print i; }
+swap(x, tmp);";
+    assert_eq!(
+        expand_source(source).unwrap(),
+        "for (i_1 in 0 .. (1 + 2)) {\n    print i;\n}\nvar tmp_2 = x;\nx = tmp;\ntmp = tmp_2;\n"
+    );
+    assert_eq!(
+        expand_source("macro loop() => { loop() } loop()")
+            .unwrap_err()
+            .to_string(),
+        "[line 1] Error at 'loop': Macro expansion is too deeply nested."
+    );
+    assert_eq!(
+        expand_source("macro twice($x:expr) => { $x; $x; }\ntwice();")
+            .unwrap_err()
+            .to_string(),
+        "[line 2] Error at 'twice': Invocation does not match the macro's pattern."
+    );
+    assert!(expand_source("macro bad() => { $y }").is_err());
+    assert!(expand_source("macro bare $x:expr => { $x }").is_err());
+}
+
+#[test]
+fn test_macro_invocation_sites() {
+    use crate::tokenizer::Tokenizer;
+
+    let tokenizer = Tokenizer::new(
+        "macro twice($x:expr) => {
+    $x;
+    $x;
+}
+var twice = point.twice;
+twice(tick);"
+            .into(),
+    );
+    let tokens = expand(tokenizer.iter()).unwrap();
+    assert_eq!(render(&tokens), "var twice = point.twice;\ntick;\ntick;\n");
+    // The template spans lines 2 and 3, but its tokens report the call's line
+    let lines: Vec<u32> = tokens[7..].iter().map(Token::line).collect();
+    assert_eq!(lines, [6, 6, 6, 6]);
+}
+
+#[test]
+fn test_render_reruns() {
+    use crate::interpreter::{Environment, RuntimeValue};
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    let tokenizer = Tokenizer::new(
+        "macro swap($a:ident, $b:ident) => { var tmp = $a; $a = $b; $b = tmp; }
+var x = 1;
+var tmp = 2;
+var tmp_1 = 3;
+swap(x, tmp);"
+            .into(),
+    );
+    let rendered = render(&expand(tokenizer.iter()).unwrap());
+    assert!(rendered.ends_with("var tmp_1_ = x;\nx = tmp;\ntmp = tmp_1_;\n"));
+
+    let tokenizer = Tokenizer::new(rendered);
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let mut variables = Environment::new();
+    parser
+        .parse_program()
+        .unwrap()
+        .eval(&mut variables)
+        .unwrap();
+
+    assert_eq!(variables["x"], RuntimeValue::Number(2.0));
+    assert_eq!(variables["tmp"], RuntimeValue::Number(1.0));
+    assert_eq!(variables["tmp_1"], RuntimeValue::Number(3.0));
+}
@@ -1,9 +1,15 @@
+mod bigint;
+mod checker;
+mod encoding;
 mod interpreter;
+mod macros;
+mod modules;
 mod parser;
 mod token;
 mod tokenizer;
+mod unicode;
 use core::result::Result::Ok;
-use std::collections::HashMap;
+use interpreter::Environment;
 use interpreter::RuntimeValue;
 use parser::Expression;
 use parser::Parser;
@@ -12,7 +18,7 @@ use std::env;
 use std::fs;
 use std::io::{self, Write};
 use std::process::ExitCode;
-use tokenizer::Tokenizer;
+use tokenizer::{TokenIter, Tokenizer};
 
 fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
@@ -81,14 +87,17 @@ fn main() -> ExitCode {
             let mut iter = tokenizer.iter().peekable();
             let mut parser = Parser::new(&mut iter);
             let e = parser.parse();
-            let mut varialbes = HashMap::new();
+            let mut variables = Environment::new();
             match e {
                 Ok(e) => {
-                    let eval = e.eval(&mut varialbes);
+                    let eval = e.eval(&mut variables);
                     match eval {
                         Ok(RuntimeValue::Number(x)) => {
                             println!("{}", x);
                         }
+                        Ok(x @ (RuntimeValue::Integer(_) | RuntimeValue::BigInteger(_))) => {
+                            println!("{}", x);
+                        }
                         Ok(RuntimeValue::String(x)) => {
                             println!("{}", x);
                         }
@@ -115,6 +124,57 @@ fn main() -> ExitCode {
                 }
             }
         }
+        "check" => {
+            let file_contents = match fs::read_to_string(filename) {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("Cannot read '{filename}': {e}");
+                    return ExitCode::from(66);
+                }
+            };
+            let tokenizer = Tokenizer::new(file_contents);
+            let tokens = match macros::expand(tokenizer.iter()) {
+                Ok(tokens) => tokens,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(65);
+                }
+            };
+            let mut iter = TokenIter::replay(tokens).peekable();
+            let mut parser = Parser::new(&mut iter);
+            match parser.parse_program() {
+                Ok(program) => {
+                    let errors = checker::check(&program);
+                    for error in errors.iter() {
+                        eprintln!("{}", error);
+                    }
+                    if !errors.is_empty() {
+                        return ExitCode::from(65);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(65);
+                }
+            }
+        }
+        "expand" => {
+            let file_contents = match fs::read_to_string(filename) {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("Cannot read '{filename}': {e}");
+                    return ExitCode::from(66);
+                }
+            };
+            let tokenizer = Tokenizer::new(file_contents);
+            match macros::expand(tokenizer.iter()) {
+                Ok(tokens) => print!("{}", macros::render(&tokens)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(65);
+                }
+            }
+        }
         "run" => {
             let file_contents = match fs::read_to_string(filename) {
                 Ok(f) => f,
@@ -125,13 +185,24 @@ fn main() -> ExitCode {
                 return ExitCode::SUCCESS;
             }
             let tokenizer = Tokenizer::new(file_contents);
-            let mut iter = tokenizer.iter().peekable();
+            let tokens = match macros::expand(tokenizer.iter()) {
+                Ok(tokens) => tokens,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return ExitCode::from(65);
+                }
+            };
+            let mut iter = TokenIter::replay(tokens).peekable();
             let mut parser = Parser::new(&mut iter);
+            modules::enter_main(filename);
             let e = parser.parse_program();
+            for warning in parser.warnings() {
+                eprintln!("{}", warning);
+            }
             match e {
                 Ok(e) => {
-                    let mut varialbes = HashMap::new();
-                    let s = e.eval(&mut varialbes);
+                    let mut variables = Environment::new();
+                    let s = e.eval(&mut variables);
                     match s {
                         Ok(_) => {}
                         Err(e) => {
@@ -141,7 +212,7 @@ fn main() -> ExitCode {
                     }
                 }
                 Err(e) => {
-                    println!("");
+                    eprintln!("{}", e);
                     return ExitCode::from(65);
                 }
             }
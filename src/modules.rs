@@ -0,0 +1,221 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::interpreter::{Environment, RuntimeError, RuntimeValue};
+use crate::macros;
+use crate::parser::Parser;
+use crate::token::Token;
+use crate::tokenizer::{TokenIter, Tokenizer};
+
+use anyhow::{anyhow, Context, Ok, Result};
+
+#[derive(Default)]
+struct Registry {
+    loaded: HashMap<PathBuf, RuntimeValue>,
+    loading: Vec<PathBuf>,
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::default();
+}
+
+/// Registers the script passed to `run` so its imports resolve relative to it.
+pub fn enter_main(path: &str) {
+    if let std::result::Result::Ok(path) = fs::canonicalize(path) {
+        REGISTRY.with_borrow_mut(|registry| registry.loading.push(path));
+    }
+}
+
+/// Loads the module at `path`, executing it the first time it is imported.
+pub fn import(path: &str) -> Result<RuntimeValue> {
+    let resolved = resolve(path)?;
+    let cached = REGISTRY.with_borrow_mut(|registry| {
+        if let Some(module) = registry.loaded.get(&resolved) {
+            return Ok(Some(module.clone()));
+        }
+        if let Some(start) = registry.loading.iter().position(|p| *p == resolved) {
+            let cycle = registry.loading[start..]
+                .iter()
+                .chain(iter::once(&resolved))
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(anyhow!("Import cycle detected: {cycle}"));
+        }
+        registry.loading.push(resolved.clone());
+        Ok(None)
+    })?;
+    if let Some(module) = cached {
+        return Ok(module);
+    }
+
+    // The registry is not borrowed while the module runs so it can import others.
+    let module = execute(&resolved);
+    REGISTRY.with_borrow_mut(|registry| {
+        registry.loading.pop();
+        let module = module?;
+        registry.loaded.insert(resolved, module.clone());
+        Ok(module)
+    })
+}
+
+fn resolve(path: &str) -> Result<PathBuf> {
+    let mut file = PathBuf::from(path);
+    if file.extension().is_none() {
+        file.set_extension("lox");
+    }
+    let importer_dir = REGISTRY
+        .with_borrow(|registry| registry.loading.last().cloned())
+        .and_then(|importer| importer.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."));
+    let search_path = env::var_os("LOX_PATH")
+        .map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    iter::once(importer_dir)
+        .chain(search_path)
+        .map(|dir| dir.join(&file))
+        .find(|candidate| candidate.is_file())
+        .context(format!("Cannot find module '{path}'."))
+        .and_then(|found| Ok(fs::canonicalize(found)?))
+}
+
+fn execute(path: &Path) -> Result<RuntimeValue> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Cannot read module '{}'.", path.display()))?;
+    let tokenizer = Tokenizer::new(source);
+    if let Some(Token::Error(err)) = tokenizer
+        .iter()
+        .find(|token| matches!(token, Token::Error(_)))
+    {
+        return Err(anyhow!("Error in module '{}': {err}", path.display()));
+    }
+
+    let tokens = macros::expand(tokenizer.iter())
+        .map_err(|e| anyhow!("Error in module '{}': {e}", path.display()))?;
+    let mut iter = TokenIter::replay(tokens).peekable();
+    let mut parser = Parser::new(&mut iter);
+    let program = parser
+        .parse_program()
+        .map_err(|e| anyhow!("Error in module '{}': {e}", path.display()))?;
+    for warning in parser.warnings() {
+        writeln!(io::stderr(), "{}", warning)?;
+    }
+
+    let mut variables = Environment::new();
+    program
+        .eval(&mut variables)
+        .map_err(|e| in_module(e, path))?;
+    Ok(RuntimeValue::Module(
+        path.display().to_string(),
+        Arc::new(variables.into_iter().collect()),
+    ))
+}
+
+/// Names the module a runtime error happened in, keeping the line inside it.
+/// Errors from nested imports already name their module, and thrown values
+/// pass through untouched so importers can still catch them.
+fn in_module(error: anyhow::Error, path: &Path) -> anyhow::Error {
+    match error.downcast::<RuntimeError>() {
+        std::result::Result::Ok(RuntimeError::Error(message, line))
+            if !message.starts_with("Error in module '") =>
+        {
+            let message = format!("Error in module '{}': {message}", path.display());
+            RuntimeError::Error(message, line).into()
+        }
+        std::result::Result::Ok(error) => error.into(),
+        Err(error) => anyhow!("Error in module '{}': {error}", path.display()),
+    }
+}
+
+/// A scratch directory for one test's modules, removed when dropped.
+#[cfg(test)]
+struct ModuleDir(PathBuf);
+
+#[cfg(test)]
+impl ModuleDir {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("lox-modules-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        ModuleDir(dir)
+    }
+    fn join(&self, file: &str) -> PathBuf {
+        self.0.join(file)
+    }
+}
+
+#[cfg(test)]
+impl Drop for ModuleDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn test_import_namespace() {
+    let dir = ModuleDir::new("namespace");
+    fs::write(dir.join("util.lox"), "var answer = 42; { var hidden = 1; }").unwrap();
+    fs::write(
+        dir.join("main.lox"),
+        "import \"util.lox\"; import \"util\" as u; var a = util.answer;",
+    )
+    .unwrap();
+
+    let util = import(dir.join("util").to_str().unwrap()).unwrap();
+    let RuntimeValue::Module(_, members) = &util else {
+        panic!("expected module, got {util:?}");
+    };
+    assert_eq!(members.get("answer"), Some(&RuntimeValue::Number(42.0)));
+    assert!(!members.contains_key("hidden"));
+
+    let main = import(dir.join("main.lox").to_str().unwrap()).unwrap();
+    let RuntimeValue::Module(_, main_members) = &main else {
+        panic!("expected module, got {main:?}");
+    };
+    assert_eq!(main_members.get("a"), Some(&RuntimeValue::Number(42.0)));
+    // Both imports share the cached namespace instead of re-running util.lox
+    match (&main_members["util"], &main_members["u"], &util) {
+        (RuntimeValue::Module(_, a), RuntimeValue::Module(_, b), RuntimeValue::Module(_, c)) => {
+            assert!(Arc::ptr_eq(a, b) && Arc::ptr_eq(a, c));
+        }
+        _ => panic!("expected modules"),
+    }
+}
+
+#[test]
+fn test_import_cycle() {
+    let dir = ModuleDir::new("cycle");
+    fs::write(dir.join("a.lox"), "import \"b.lox\";").unwrap();
+    fs::write(dir.join("b.lox"), "import \"a.lox\";").unwrap();
+
+    let err = import(dir.join("a.lox").to_str().unwrap()).unwrap_err();
+    let message = err.to_string();
+    let b = fs::canonicalize(dir.join("b.lox")).unwrap();
+    let prefix = format!("Error in module '{}': Import cycle detected: ", b.display());
+    assert!(message.starts_with(&prefix), "{message}");
+    assert!(message.contains("a.lox -> "), "{message}");
+    assert!(message.contains("b.lox -> "), "{message}");
+}
+
+#[test]
+fn test_module_runtime_error() {
+    let dir = ModuleDir::new("runtime-error");
+    fs::write(dir.join("bad.lox"), "var a = 1;\nprint b;").unwrap();
+
+    let err = import(dir.join("bad.lox").to_str().unwrap()).unwrap_err();
+    let path = fs::canonicalize(dir.join("bad.lox")).unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Error in module '{}': Undefined variable 'b'.\n[line 2]",
+            path.display()
+        )
+    );
+}
@@ -1,6 +1,9 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Binary;
 use std::iter::Peekable;
 
+use crate::bigint::BigInt;
 use crate::parser;
 use crate::tokenizer::Tokenizer;
 use crate::{token::Token, tokenizer::TokenIter};
@@ -8,6 +11,7 @@ use anyhow::Error;
 use anyhow::Ok;
 use anyhow::Result;
 use anyhow::{anyhow, Context};
+use bytes::Bytes;
 #[derive(Debug, PartialEq)]
 pub enum Operator {
     Add,
@@ -24,6 +28,7 @@ pub enum Operator {
     Bang,
     And,
     Or,
+    Coalesce,
 }
 
 #[derive(Debug, PartialEq)]
@@ -31,20 +36,165 @@ pub enum Expression {
     Binary(Box<Expression>, Operator, Box<Expression>),
     Unary(Operator, Box<Expression>),
     Number(f64),
+    // A literal written with a fraction, which stays a float even when whole
+    Float(f64),
+    // An integer literal too large for a float to hold exactly
+    Integer(BigInt),
     Boolean(bool),
     String(String),
+    Bytes(Bytes),
     Group(Box<Expression>),
     Assign(String, Box<Expression>),
     Identifier(String),
+    Get(Box<Expression>, String),
+    Call(Box<Expression>, Vec<Expression>, u32),
+    Index(Box<Expression>, Box<Expression>),
+    // The receiver of a `?.`: a nil value short-circuits the rest of the chain
+    Optional(Box<Expression>),
+    // `value |> f(args)`, calling `f(value, args)`
+    Pipeline(Box<Expression>, Box<Expression>),
+    // `start..end`, or `start..=end` when inclusive
+    Range(Box<Expression>, Box<Expression>, bool),
+    Tuple(Vec<Expression>),
+    // `name: value` in a call's argument list
+    NamedArgument(String, Box<Expression>),
     Nil,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Type {
+    Number,
+    String,
+    Bool,
+    Nil,
+    Any,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Pattern {
+    Literal(Expression),
+    Range(f64, f64, bool),
+    // `Shape.Rect(w, h)`: an enum, one of its variants and names for its payload
+    Variant(String, String, Vec<String>),
+    Wildcard,
+}
+
+impl Pattern {
+    /// Names the pattern binds, skipping `_` placeholders.
+    pub fn names(&self) -> Vec<&String> {
+        match self {
+            Pattern::Variant(_, _, fields) => fields.iter().filter(|name| *name != "_").collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BindingPattern {
+    Name(String),
+    List(Vec<BindingPattern>, Option<String>),
+    Map(Vec<(String, BindingPattern)>),
+    Tuple(Vec<BindingPattern>),
+}
+
+impl BindingPattern {
+    pub fn names(&self) -> Vec<&String> {
+        match self {
+            BindingPattern::Name(name) => vec![name],
+            BindingPattern::List(items, rest) => items
+                .iter()
+                .flat_map(BindingPattern::names)
+                .chain(rest.iter())
+                .collect(),
+            BindingPattern::Tuple(items) => items.iter().flat_map(BindingPattern::names).collect(),
+            BindingPattern::Map(entries) => entries
+                .iter()
+                .flat_map(|(_, target)| target.names())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Associativity {
+    Left,
+    Right,
+    // Chaining the operator, as in `a..b..c`, is a syntax error
+    None,
+}
+
+/// What a binary operator builds from its two operands.
+#[derive(Debug)]
+enum Infix {
+    Binary(Operator),
+    Pipeline,
+    Range(bool),
+    // A declared operator, calling the function bound to its symbol
+    Custom(String),
+}
+
+fn is_operator_symbol(lexeme: &str) -> bool {
+    lexeme.chars().all(|c| "+-*/=<>!:|.?".contains(c))
+}
+
+/// Whether `tokens` spell out `symbol` with nothing between them.
+fn spells<'t>(mut tokens: impl Iterator<Item = &'t Token>, symbol: &[String]) -> bool {
+    let mut end = None;
+    for lexeme in symbol {
+        let Some(token) = tokens.next() else {
+            return false;
+        };
+        match token.span() {
+            Some((text, line, col))
+                if text == lexeme && (end.is_none() || end == Some((line, col))) =>
+            {
+                end = Some((line, col + text.chars().count() as u32));
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub patterns: Vec<Pattern>,
+    pub guard: Option<Expression>,
+    pub body: Box<Statement>,
+}
+
+impl MatchArm {
+    /// Names bound by any of the arm's patterns, visible in its guard and body.
+    pub fn names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.patterns.iter().flat_map(Pattern::names).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
 #[derive(Debug)]
 pub enum Statement {
-    Print(Box<Expression>),
-    ExprStmt(Box<Expression>),
+    Print(Box<Expression>, u32),
+    ExprStmt(Box<Expression>, u32),
     Program(Vec<Statement>),
-    Declaration(String, Box<Expression>),
+    Declaration(String, Option<Type>, Box<Expression>, u32),
+    Const(String, Option<Type>, Box<Expression>, u32),
+    Match(Box<Expression>, Vec<MatchArm>, u32),
+    Block(Vec<Statement>),
+    Throw(Box<Expression>, u32),
+    Try(
+        Vec<Statement>,
+        Option<(String, Vec<Statement>)>,
+        Option<Vec<Statement>>,
+    ),
+    Import(String, String, u32),
+    ForIn(String, Box<Expression>, Box<Statement>, u32),
+    DestructureDeclaration(BindingPattern, Box<Expression>, u32),
+    DestructureAssign(BindingPattern, Box<Expression>, u32),
+    Struct(String, Vec<String>),
+    // Each variant with the field names of its payload, empty for none
+    Enum(String, Vec<(String, Vec<String>)>),
 }
 
 impl Expression {
@@ -66,6 +216,7 @@ impl Expression {
                     Operator::Bang => "!".to_string(),
                     Operator::And => "&&".to_string(),
                     Operator::Or => "||".to_string(),
+                    Operator::Coalesce => "??".to_string(),
                 };
                 return "(".to_owned() + &op + " " + &left.pprint() + " " + &right.pprint() + ")";
             }
@@ -85,96 +236,100 @@ impl Expression {
                     Operator::Bang => "!".to_string(),
                     Operator::And => "&&".to_string(),
                     Operator::Or => "||".to_string(),
+                    Operator::Coalesce => "??".to_string(),
                 };
                 return "(".to_owned() + &op + " " + &expr.pprint() + ")";
             }
-            Expression::Number(val) => {
+            Expression::Number(val) | Expression::Float(val) => {
                 if val.trunc() == *val {
                     format!("{:.1}", val)
                 } else {
                     format!("{}", val)
                 }
             }
+            Expression::Integer(val) => format!("{}.0", val),
             Expression::Boolean(val) => val.to_string(),
             Expression::String(val) => val.to_owned(),
+            Expression::Bytes(val) => format!("b\"{}\"", val.escape_ascii()),
             Expression::Nil => "nil".to_owned(),
             Expression::Group(expr) => "(group ".to_owned() + &expr.pprint() + ")",
             Expression::Identifier(s) => s.to_owned(),
-            Expression::Assign(s, e) => todo!(),
+            Expression::Assign(s, e) => "(= ".to_owned() + s + " " + &e.pprint() + ")",
+            Expression::Get(e, name) => "(. ".to_owned() + &e.pprint() + " " + name + ")",
+            Expression::Call(callee, args, _) => {
+                let args: String = args
+                    .iter()
+                    .map(|arg| " ".to_owned() + &arg.pprint())
+                    .collect();
+                "(call ".to_owned() + &callee.pprint() + &args + ")"
+            }
+            Expression::Index(e, index) => {
+                "(index ".to_owned() + &e.pprint() + " " + &index.pprint() + ")"
+            }
+            Expression::Optional(e) => "(? ".to_owned() + &e.pprint() + ")",
+            Expression::Pipeline(value, f) => {
+                "(|> ".to_owned() + &value.pprint() + " " + &f.pprint() + ")"
+            }
+            Expression::Range(start, end, inclusive) => {
+                let op = if *inclusive { "(..= " } else { "(.. " };
+                op.to_owned() + &start.pprint() + " " + &end.pprint() + ")"
+            }
+            Expression::NamedArgument(name, value) => {
+                "(: ".to_owned() + name + " " + &value.pprint() + ")"
+            }
+            Expression::Tuple(items) => {
+                let items: String = items
+                    .iter()
+                    .map(|item| " ".to_owned() + &item.pprint())
+                    .collect();
+                "(tuple".to_owned() + &items + ")"
+            }
         }
     }
 }
 pub struct Parser<'a> {
     iter: &'a mut Peekable<TokenIter<'a>>,
+    // Tokens taken from `iter` to look past the next one, not yet consumed
+    lookahead: VecDeque<Token>,
+    warnings: Vec<String>,
+    // Names declared in each enclosing block, mapped to whether they are constant
+    scopes: Vec<HashMap<String, bool>>,
+    // Operators declared with `infix`: the lexemes spelling each one, its
+    // precedence and associativity, longest symbols first
+    operators: Vec<(Vec<String>, u8, Associativity)>,
 }
 impl<'a> Parser<'a> {
     pub fn new(iter: &'a mut Peekable<TokenIter<'a>>) -> Self {
-        Parser { iter: iter }
+        Parser {
+            iter,
+            lookahead: VecDeque::new(),
+            warnings: Vec::new(),
+            scopes: vec![HashMap::new()],
+            operators: Vec::new(),
+        }
+    }
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
     fn assert_next(&mut self, token: Token) -> Result<()> {
-        let next = self.iter.next();
+        let next = self.next();
         if next == None {
             return Err(anyhow!("Undexpected end of input."));
         }
-        if next.as_ref() != Some(&token) {
+        if next.as_ref().map(std::mem::discriminant) != Some(std::mem::discriminant(&token)) {
             return Err(anyhow!("Expected {:?} actual {:?}", token, next.as_ref()));
         }
         Ok(())
     }
-    fn operator(&mut self) -> Result<Operator> {
-        let next = self.next().context("Expected operator, got EOF")?;
-        let op = match next {
-            Token::LeftParen(_, _, _) => todo!(),
-            Token::RightParen(_, _, _) => todo!(),
-            Token::LeftBrace(_, _, _) => todo!(),
-            Token::RightBrace(_, _, _) => todo!(),
-            Token::Star(_, _, _) => Operator::Multiply,
-            Token::Dot(_, _, _) => todo!(),
-            Token::Comma(_, _, _) => todo!(),
-            Token::Plus(_, _, _) => Operator::Add,
-            Token::Minus(_, _, _) => Operator::Subtract,
-            Token::Semicolon(_, _, _) => todo!(),
-            Token::Slash(_, _, _) => Operator::Divide,
-            Token::Equal(_, _, _) => todo!(),
-            Token::EqualEqual(_, _, _) => todo!(),
-            Token::Bang(_, _, _) => todo!(),
-            Token::BangEqual(_, _, _) => todo!(),
-            Token::Less(_, _, _) => todo!(),
-            Token::LessEqual(_, _, _) => todo!(),
-            Token::Greater(_, _, _) => todo!(),
-            Token::GreaterEqual(_, _, _) => todo!(),
-            Token::Colon(_, _, _) => todo!(),
-            Token::Error(_) => todo!(),
-            Token::Number(_, _, _, _) => todo!(),
-            Token::Identifier(_, _, _, _) => todo!(),
-            Token::String(_, _, _, _) => todo!(),
-            Token::And(_, _, _) => todo!(),
-            Token::Class(_, _, _) => todo!(),
-            Token::Else(_, _, _) => todo!(),
-            Token::False(_, _, _) => todo!(),
-            Token::For(_, _, _) => todo!(),
-            Token::Fun(_, _, _) => todo!(),
-            Token::If(_, _, _) => todo!(),
-            Token::Nil(_, _, _) => todo!(),
-            Token::Or(_, _, _) => todo!(),
-            Token::Print(_, _, _) => todo!(),
-            Token::Return(_, _, _) => todo!(),
-            Token::Super(_, _, _) => todo!(),
-            Token::This(_, _, _) => todo!(),
-            Token::True(_, _, _) => todo!(),
-            Token::Var(_, _, _) => todo!(),
-            Token::While(_, _, _) => todo!(),
-            Token::EndOfFile => todo!(),
-        };
-        Ok(op)
-    }
     fn primary(&mut self) -> Result<Expression> {
-        let next = self.iter.next().context("Expected token got EOF")?;
+        let next = self.next().context("Expected token got EOF")?;
         match next {
             Token::LeftParen(_, line, col) => {
                 let e = self.expression()?;
+                if let Some(Token::Comma(_, _, _)) = self.peek() {
+                    return self.tuple(e, line, col);
+                }
                 let expected = self
-                    .iter
                     .next()
                     .context(format!("Expected ')' at line:{line} col:{col}"))?;
 
@@ -183,210 +338,531 @@ impl<'a> Parser<'a> {
                     _ => Err(anyhow!("Expected ')' at line:{line} col:{col}")),
                 }
             }
-            Token::Number(_, _, _, n) => Ok(Expression::Number(n)),
+            Token::Number(lexeme, _, _, n) => match BigInt::parse(&lexeme) {
+                // Beyond 2^53 a float can no longer hold every integer
+                Some(big) if big.to_i64().map_or(true, |i| i.unsigned_abs() > 1 << 53) => {
+                    Ok(Expression::Integer(big))
+                }
+                Some(_) => Ok(Expression::Number(n)),
+                None => Ok(Expression::Float(n)),
+            },
             Token::True(_, _, _) => Ok(Expression::Boolean(true)),
             Token::False(_, _, _) => Ok(Expression::Boolean(false)),
             Token::Nil(_, _, _) => Ok(Expression::Nil),
             Token::String(_, _, _, s) => Ok(Expression::String(s)),
+            Token::Bytes(_, _, _, b) => Ok(Expression::Bytes(b.into())),
             Token::Identifier(identifier, _, _, _) => Ok(Expression::Identifier(identifier)),
             _ => Err(anyhow!("Unexpected")),
         }
     }
+    /// Parses the rest of `(a, b, ...)` once the first element and a comma are seen.
+    /// A trailing comma is allowed, so `(a,)` is a one-element tuple.
+    fn tuple(&mut self, first: Expression, line: u32, col: u32) -> Result<Expression> {
+        let mut items = vec![first];
+        while let Some(Token::Comma(_, _, _)) = self.peek() {
+            self.next();
+            if let Some(Token::RightParen(_, _, _)) = self.peek() {
+                break;
+            }
+            items.push(self.expression()?);
+        }
+        self.assert_next(Token::RightParen(")".into(), 0, 0))
+            .context(format!("Expected ')' at line:{line} col:{col}"))?;
+        Ok(Expression::Tuple(items))
+    }
     fn uanary(&mut self) -> Result<Expression> {
-        let next = self.iter.peek().context("Unexpected EOF.")?;
+        let next = self.peek().context("Unexpected EOF.")?;
         match next {
             Token::Bang(_, _, _) => {
-                self.iter.next();
+                self.next();
                 Ok(Expression::Unary(Operator::Bang, Box::new(self.uanary()?)))
             }
             Token::Minus(_, _, _) => {
-                self.iter.next();
+                self.next();
 
                 Ok(Expression::Unary(
                     Operator::Subtract,
                     Box::new(self.uanary()?),
                 ))
             }
-            _ => self.primary(),
+            _ => self.call(),
         }
     }
-    fn factor(&mut self) -> Result<Expression> {
-        let mut left = self.uanary()?;
+    fn call(&mut self) -> Result<Expression> {
+        let mut expr = self.primary()?;
         loop {
-            let next = self.iter.peek();
-            match next {
-                Some(Token::Star(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.uanary()?;
-                    left = Expression::Binary(Box::new(left), Operator::Multiply, Box::new(right));
+            match self.peek() {
+                Some(Token::Dot(_, line, _)) => {
+                    let line = *line;
+                    self.next();
+                    match self.next() {
+                        Some(Token::Identifier(name, _, _, _)) => {
+                            expr = Expression::Get(Box::new(expr), name);
+                        }
+                        // `t.0.1` lexes its indices as the number 0.1
+                        Some(Token::Number(lexeme, _, _, _)) => {
+                            for index in lexeme.split('.') {
+                                expr = Expression::Get(Box::new(expr), index.to_owned());
+                            }
+                        }
+                        _ => {
+                            return Err(anyhow!(
+                                "[line {line}] Error: Expect property name after '.'."
+                            ))
+                        }
+                    }
                 }
-                Some(Token::Slash(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.uanary()?;
-                    left = Expression::Binary(Box::new(left), Operator::Divide, Box::new(right));
+                Some(Token::LeftParen(_, line, _)) => {
+                    let line = *line;
+                    self.next();
+                    let args = self.arguments()?;
+                    expr = Expression::Call(Box::new(expr), args, line);
                 }
-                _ => break,
-            }
-        }
-        Ok(left)
-    }
-    fn term(&mut self) -> Result<Expression> {
-        let mut left = self.factor()?;
-        loop {
-            let next = self.iter.peek();
-            match next {
-                Some(Token::Plus(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.factor()?;
-                    left = Expression::Binary(Box::new(left), Operator::Add, Box::new(right));
+                Some(Token::LeftBracket(_, _, _)) => {
+                    self.next();
+                    expr = Expression::Index(Box::new(expr), Box::new(self.index()?));
                 }
-                Some(Token::Minus(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.factor()?;
-                    left = Expression::Binary(Box::new(left), Operator::Subtract, Box::new(right));
+                Some(Token::QuestionDot(_, line, _)) => {
+                    let line = *line;
+                    self.next();
+                    let receiver = Box::new(Expression::Optional(Box::new(expr)));
+                    expr = match self.next() {
+                        Some(Token::Identifier(name, _, _, _)) => Expression::Get(receiver, name),
+                        Some(Token::LeftBracket(_, _, _)) => {
+                            Expression::Index(receiver, Box::new(self.index()?))
+                        }
+                        _ => {
+                            return Err(anyhow!(
+                                "[line {line}] Error: Expect property name or '[' after '?.'."
+                            ))
+                        }
+                    };
                 }
-                _ => break,
+                _ => return Ok(expr),
             }
         }
-        Ok(left)
     }
-    fn comparison(&mut self) -> Result<Expression> {
-        let mut left = self.term()?;
-        loop {
-            let op: Option<&Token> = self.iter.peek();
-            match op {
-                Some(Token::Greater(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.term()?;
-                    left = Expression::Binary(Box::new(left), Operator::Greater, Box::new(right));
-                }
-                Some(Token::GreaterEqual(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.term()?;
-                    left =
-                        Expression::Binary(Box::new(left), Operator::GreaterEqual, Box::new(right));
-                }
-                Some(Token::Less(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.term()?;
-                    left = Expression::Binary(Box::new(left), Operator::Less, Box::new(right));
-                }
-                Some(Token::LessEqual(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.term()?;
-                    left = Expression::Binary(Box::new(left), Operator::LessEqual, Box::new(right));
-                }
-                _ => break,
-            }
-        }
-        Ok(left)
+    fn index(&mut self) -> Result<Expression> {
+        let index = self.expression()?;
+        self.assert_next(Token::RightBracket("]".into(), 0, 0))
+            .context("Expected ']' after index.")?;
+        Ok(index)
     }
-
-    fn equality(&mut self) -> Result<Expression> {
-        let mut left = self.comparison()?;
+    fn arguments(&mut self) -> Result<Vec<Expression>> {
+        let mut args = Vec::new();
+        if let Some(Token::RightParen(_, _, _)) = self.peek() {
+            self.next();
+            return Ok(args);
+        }
         loop {
-            let op = self.iter.peek();
-            match op {
-                Some(Token::BangEqual(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.comparison()?;
-                    left = Expression::Binary(Box::new(left), Operator::BangEqual, Box::new(right));
+            let arg = match self.expression()? {
+                Expression::Identifier(name)
+                    if matches!(self.peek(), Some(Token::Colon(_, _, _))) =>
+                {
+                    self.next();
+                    Expression::NamedArgument(name, Box::new(self.expression()?))
                 }
-                Some(Token::EqualEqual(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.comparison()?;
-                    left =
-                        Expression::Binary(Box::new(left), Operator::EqualEqual, Box::new(right));
+                arg => arg,
+            };
+            args.push(arg);
+            match self.next() {
+                Some(Token::Comma(_, _, _)) => continue,
+                Some(Token::RightParen(_, _, _)) => return Ok(args),
+                next => {
+                    let line = next.map(|token| token.line()).unwrap_or_default();
+                    return Err(anyhow!("[line {line}] Error: Expect ')' after arguments."));
                 }
-                _ => break,
             }
         }
-        Ok(left)
     }
-    fn and(&mut self) -> Result<Expression> {
-        let mut left = self.equality()?;
-        loop {
-            let op = self.iter.peek();
-            match op {
-                Some(Token::And(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.equality()?;
-                    left = Expression::Binary(Box::new(left), Operator::And, Box::new(right));
-                }
-                _ => break,
+    /// Parses a chain of binary operators by precedence climbing, leaving
+    /// operators that bind looser than `min_precedence` to the caller.
+    fn binary(&mut self, min_precedence: u8) -> Result<Expression> {
+        let mut left = self.uanary()?;
+        // The precedence of a non-associative operator that was just applied
+        let mut chained = None;
+        while let Some((infix, precedence, associativity, length)) = self.infix_operator() {
+            if precedence < min_precedence || chained == Some(precedence) {
+                break;
+            }
+            let line = self.peek().map_or(0, Token::line);
+            for _ in 0..length {
+                self.next();
             }
+            let right = match associativity {
+                Associativity::Right => self.binary(precedence)?,
+                Associativity::Left | Associativity::None => self.binary(precedence + 1)?,
+            };
+            if associativity == Associativity::None {
+                chained = Some(precedence);
+            }
+            let (left_operand, right) = (Box::new(left), Box::new(right));
+            left = match infix {
+                Infix::Binary(op) => Expression::Binary(left_operand, op, right),
+                Infix::Pipeline => Expression::Pipeline(left_operand, right),
+                Infix::Range(inclusive) => Expression::Range(left_operand, right, inclusive),
+                Infix::Custom(symbol) => Expression::Call(
+                    Box::new(Expression::Identifier(symbol)),
+                    vec![*left_operand, *right],
+                    line,
+                ),
+            };
         }
         Ok(left)
     }
-    fn or(&mut self) -> Result<Expression> {
-        let mut left = self.and()?;
-        loop {
-            let op = self.iter.peek();
-            match op {
-                Some(Token::Or(_, _, _)) => {
-                    self.iter.next();
-                    let right = self.and()?;
-                    left = Expression::Binary(Box::new(left), Operator::Or, Box::new(right));
-                }
-                _ => break,
+    /// Looks up the binary operator the next tokens spell, along with its
+    /// precedence, associativity and how many tokens it takes up. Built-in
+    /// precedences run from 10 for `|>` to 90 for `*` and `/`.
+    fn infix_operator(&mut self) -> Option<(Infix, u8, Associativity, usize)> {
+        let longest = self
+            .operators
+            .iter()
+            .map(|(symbol, _, _)| symbol.len())
+            .max();
+        if let Some(longest) = longest {
+            self.peek_nth(longest - 1);
+        }
+        for (symbol, precedence, associativity) in &self.operators {
+            if spells(self.lookahead.iter(), symbol) {
+                return Some((
+                    Infix::Custom(symbol.concat()),
+                    *precedence,
+                    *associativity,
+                    symbol.len(),
+                ));
             }
         }
-        Ok(left)
+        let (infix, precedence) = match self.peek()? {
+            Token::PipeGreater(_, _, _) => (Infix::Pipeline, 10),
+            Token::Or(_, _, _) => (Infix::Binary(Operator::Or), 20),
+            Token::QuestionQuestion(_, _, _) => (Infix::Binary(Operator::Coalesce), 30),
+            Token::And(_, _, _) => (Infix::Binary(Operator::And), 40),
+            Token::EqualEqual(_, _, _) => (Infix::Binary(Operator::EqualEqual), 50),
+            Token::BangEqual(_, _, _) => (Infix::Binary(Operator::BangEqual), 50),
+            Token::Greater(_, _, _) => (Infix::Binary(Operator::Greater), 60),
+            Token::GreaterEqual(_, _, _) => (Infix::Binary(Operator::GreaterEqual), 60),
+            Token::Less(_, _, _) => (Infix::Binary(Operator::Less), 60),
+            Token::LessEqual(_, _, _) => (Infix::Binary(Operator::LessEqual), 60),
+            Token::DotDot(_, _, _) => (Infix::Range(false), 70),
+            Token::DotDotEqual(_, _, _) => (Infix::Range(true), 70),
+            Token::Plus(_, _, _) => (Infix::Binary(Operator::Add), 80),
+            Token::Minus(_, _, _) => (Infix::Binary(Operator::Subtract), 80),
+            Token::Star(_, _, _) => (Infix::Binary(Operator::Multiply), 90),
+            Token::Slash(_, _, _) => (Infix::Binary(Operator::Divide), 90),
+            _ => return None,
+        };
+        let associativity = match infix {
+            Infix::Range(_) => Associativity::None,
+            _ => Associativity::Left,
+        };
+        Some((infix, precedence, associativity, 1))
     }
     fn expression(&mut self) -> Result<Expression> {
-        // let mut expr = self.or()?;
-        // Ok(expr)
-        let ident = self.iter.peek();
-        match ident {
-            Some(Token::Identifier(s, _, _, _)) => {
-                self.iter.next().context("Expected var keyword")?;
-                self.iter.next().context("Expected = token")?;
-                self.expression()
-            }
-            _ => {
-                let mut expr = self.or()?;
-                Ok(expr)
+        let expr = self.binary(0)?;
+        match self.peek() {
+            Some(Token::Equal(_, line, _)) => {
+                let line = *line;
+                self.next();
+                let value = self.expression()?;
+                match expr {
+                    Expression::Identifier(name) if self.is_constant(&name) => Err(anyhow!(
+                        "[line {line}] Error at '{name}': Cannot assign to constant."
+                    )),
+                    Expression::Identifier(name) => Ok(Expression::Assign(name, Box::new(value))),
+                    _ => Err(anyhow!(
+                        "[line {line}] Error at '=': Invalid assignment target."
+                    )),
+                }
             }
+            _ => Ok(expr),
         }
     }
     fn print_stmt(&mut self) -> Result<Statement> {
-        let print = self.iter.next().context("Expected print keyword.")?;
+        let print = self.next().context("Expected print keyword.")?;
         let print_stmt = self.expression()?;
-        let semi = self.iter.next().context("Expected ; after expression.")?;
-        Ok(Statement::Print(Box::new(print_stmt)))
+        let semi = self.next().context("Expected ; after expression.")?;
+        Ok(Statement::Print(Box::new(print_stmt), print.line()))
     }
     fn expr_stmt(&mut self) -> Result<Statement> {
-        self.iter.next().context("asd")?;
+        let line = self.peek().map_or(0, Token::line);
         let expr_stmt = self.expression()?;
-        let semi = self.iter.next().context("Expected ; after expression.")?;
-        Ok(Statement::ExprStmt(Box::new(expr_stmt)))
+        let semi = self.next().context("Expected ; after expression.")?;
+        Ok(Statement::ExprStmt(Box::new(expr_stmt), line))
     }
     fn statement(&mut self) -> Result<Statement> {
-        let next = self.iter.peek();
+        match self.keyword(&["match", "throw", "try"]) {
+            Some("match") => return self.match_stmt(),
+            Some("throw") => return self.throw_stmt(),
+            Some("try") => return self.try_stmt(),
+            _ => {}
+        }
+        let next = self.peek();
         match next {
             Some(Token::Print(_, _, _)) => self.print_stmt(),
+            Some(Token::LeftBrace(_, _, _)) => Ok(Statement::Block(self.block()?)),
+            Some(Token::For(_, _, _)) => self.for_in_stmt(),
+            Some(Token::LeftBracket(_, _, _)) => self.destructure_assign(),
+            _ => self.expr_stmt(),
+        }
+    }
+    fn declare(&mut self, name: &str, constant: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), constant);
+        }
+    }
+    fn is_constant(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+    fn block(&mut self) -> Result<Vec<Statement>> {
+        self.scopes.push(HashMap::new());
+        let statements = self.block_statements();
+        self.scopes.pop();
+        statements
+    }
+    fn block_statements(&mut self) -> Result<Vec<Statement>> {
+        self.assert_next(Token::LeftBrace("{".into(), 0, 0))
+            .context("Expected '{' before block.")?;
+        let mut statements = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::RightBrace(_, _, _)) => {
+                    self.next();
+                    return Ok(statements);
+                }
+                None => return Err(anyhow!("Expected '}}' after block.")),
+                Some(_) => statements.push(self.declaration()?),
+            }
+        }
+    }
+    fn for_in_stmt(&mut self) -> Result<Statement> {
+        let line = self.next().context("Expected for keyword.")?.line();
+        self.assert_next(Token::LeftParen("(".into(), 0, 0))
+            .context("Expected '(' after 'for'.")?;
+        if let Some(Token::Var(_, _, _)) = self.peek() {
+            self.next();
+        }
+        let name = match self.next() {
+            Some(Token::Identifier(name, _, _, _)) => name,
+            _ => return Err(anyhow!("[line {line}] Error: Expected loop variable name.")),
+        };
+        match self.next() {
+            Some(Token::Identifier(keyword, _, _, _)) if keyword == "in" => {}
             _ => {
-                
-                self.expr_stmt()
+                return Err(anyhow!(
+                    "[line {line}] Error: Expected 'in' after loop variable."
+                ))
+            }
+        }
+        let iterable = self.expression()?;
+        self.assert_next(Token::RightParen(")".into(), 0, 0))
+            .context("Expected ')' after for clauses.")?;
+        self.scopes.push(HashMap::from([(name.clone(), false)]));
+        let body = self.statement();
+        self.scopes.pop();
+        Ok(Statement::ForIn(
+            name,
+            Box::new(iterable),
+            Box::new(body?),
+            line,
+        ))
+    }
+    fn throw_stmt(&mut self) -> Result<Statement> {
+        let throw = self.next().context("Expected throw keyword.")?;
+        let value = self.expression()?;
+        self.assert_next(Token::Semicolon(";".into(), 0, 0))
+            .context("Expected ';' after thrown value.")?;
+        Ok(Statement::Throw(Box::new(value), throw.line()))
+    }
+    fn try_stmt(&mut self) -> Result<Statement> {
+        let try_token = self.next().context("Expected try keyword.")?;
+        let body = self.block()?;
+        let catch = match self.keyword(&["catch"]) {
+            Some(_) => {
+                self.next();
+                self.assert_next(Token::LeftParen("(".into(), 0, 0))
+                    .context("Expected '(' after 'catch'.")?;
+                let name = match self.next() {
+                    Some(Token::Identifier(name, _, _, _)) => name,
+                    _ => return Err(anyhow!("Expected identifier in catch clause.")),
+                };
+                self.assert_next(Token::RightParen(")".into(), 0, 0))
+                    .context("Expected ')' after catch variable.")?;
+                self.scopes.push(HashMap::from([(name.clone(), false)]));
+                let handler = self.block();
+                self.scopes.pop();
+                Some((name, handler?))
+            }
+            _ => None,
+        };
+        let finally = match self.keyword(&["finally"]) {
+            Some(_) => {
+                self.next();
+                Some(self.block()?)
+            }
+            _ => None,
+        };
+        if catch.is_none() && finally.is_none() {
+            return Err(anyhow!(
+                "[line {}] Error: Expected 'catch' or 'finally' after try block.",
+                try_token.line()
+            ));
+        }
+        Ok(Statement::Try(body, catch, finally))
+    }
+    fn match_stmt(&mut self) -> Result<Statement> {
+        let line = self.next().context("Expected match keyword.")?.line();
+        self.assert_next(Token::LeftParen("(".into(), 0, 0))
+            .context("Expected '(' after 'match'.")?;
+        let subject = self.expression()?;
+        self.assert_next(Token::RightParen(")".into(), 0, 0))
+            .context("Expected ')' after match subject.")?;
+        self.assert_next(Token::LeftBrace("{".into(), 0, 0))
+            .context("Expected '{' before match arms.")?;
+
+        let mut arms = Vec::new();
+        let mut wildcard_line = None;
+        loop {
+            match self.peek() {
+                Some(Token::RightBrace(_, _, _)) => {
+                    self.next();
+                    break;
+                }
+                None => return Err(anyhow!("Expected '}}' after match arms.")),
+                Some(token) => {
+                    let line = token.line();
+                    if let Some(wildcard_line) = wildcard_line.take() {
+                        self.warnings.push(format!(
+                            "[line {wildcard_line}] Warning: Wildcard arm is not the last arm; arms after it are unreachable."
+                        ));
+                    }
+                    let arm = self.match_arm()?;
+                    if arm.guard.is_none() && arm.patterns.contains(&Pattern::Wildcard) {
+                        wildcard_line = Some(line);
+                    }
+                    arms.push(arm);
+                }
+            }
+        }
+        Ok(Statement::Match(Box::new(subject), arms, line))
+    }
+    fn match_arm(&mut self) -> Result<MatchArm> {
+        let mut patterns = vec![self.pattern()?];
+        while let Some(Token::Pipe(_, _, _)) = self.peek() {
+            self.next();
+            patterns.push(self.pattern()?);
+        }
+        let names = patterns.iter().flat_map(Pattern::names);
+        self.scopes
+            .push(names.map(|name| (name.clone(), false)).collect());
+        let guard = match self.peek() {
+            Some(Token::If(_, _, _)) => {
+                self.next();
+                Some(self.expression()?)
             }
+            _ => None,
+        };
+        self.assert_next(Token::FatArrow("=>".into(), 0, 0))
+            .context("Expected '=>' after match pattern.")?;
+        let body = self.statement()?;
+        self.scopes.pop();
+        if let Some(Token::Comma(_, _, _)) = self.peek() {
+            self.next();
+        }
+        Ok(MatchArm {
+            patterns,
+            guard,
+            body: Box::new(body),
+        })
+    }
+    fn pattern(&mut self) -> Result<Pattern> {
+        let next = self.next().context("Expected pattern got EOF")?;
+        match next {
+            Token::Identifier(name, _, _, _) if name == "_" => Ok(Pattern::Wildcard),
+            Token::True(_, _, _) => Ok(Pattern::Literal(Expression::Boolean(true))),
+            Token::False(_, _, _) => Ok(Pattern::Literal(Expression::Boolean(false))),
+            Token::Nil(_, _, _) => Ok(Pattern::Literal(Expression::Nil)),
+            Token::String(_, _, _, s) => Ok(Pattern::Literal(Expression::String(s))),
+            Token::Bytes(_, _, _, b) => Ok(Pattern::Literal(Expression::Bytes(b.into()))),
+            Token::Number(_, _, _, n) => self.number_pattern(n),
+            Token::Identifier(name, line, _, _) => self.variant_pattern(name, line),
+            Token::Minus(_, _, _) => match self.next() {
+                Some(Token::Number(_, _, _, n)) => self.number_pattern(-n),
+                _ => Err(anyhow!("Expected number after '-' in pattern.")),
+            },
+            token => Err(anyhow!("[line {}] Error: Expected pattern.", token.line())),
         }
     }
+    /// `Shape.Rect(w, h)` binds the payload fields of a variant in order,
+    /// while a variant without a payload is written `Shape.Empty`.
+    fn variant_pattern(&mut self, name: String, line: u32) -> Result<Pattern> {
+        self.assert_next(Token::Dot(".".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error at '{name}': Expected '.' after enum name in pattern."
+            ))?;
+        let variant = self.pattern_name()?;
+        let fields = match self.peek() {
+            Some(Token::LeftParen(_, _, _)) => self.field_names(line)?,
+            _ => Vec::new(),
+        };
+        Ok(Pattern::Variant(name, variant, fields))
+    }
+    fn number_pattern(&mut self, low: f64) -> Result<Pattern> {
+        let inclusive = match self.peek() {
+            Some(Token::DotDot(_, _, _)) => false,
+            Some(Token::DotDotEqual(_, _, _)) => true,
+            _ => return Ok(Pattern::Literal(Expression::Number(low))),
+        };
+        self.next();
+        let high = match self.next() {
+            Some(Token::Number(_, _, _, n)) => n,
+            Some(Token::Minus(_, _, _)) => match self.next() {
+                Some(Token::Number(_, _, _, n)) => -n,
+                _ => return Err(anyhow!("Expected number after '-' in pattern.")),
+            },
+            _ => return Err(anyhow!("Expected upper bound in range pattern.")),
+        };
+        Ok(Pattern::Range(low, high, inclusive))
+    }
     pub fn var_decl(&mut self) -> Result<Statement> {
-        let mut next = self.iter.next().context("Expected var declaration.")?;
-        next = self.iter.next().context("Expected identifier .")?;
+        let mut next = self.next().context("Expected var declaration.")?;
+        let line = next.line();
+        if let Some(
+            Token::LeftBracket(_, _, _) | Token::LeftBrace(_, _, _) | Token::LeftParen(_, _, _),
+        ) = self.peek()
+        {
+            return self.destructure_decl(line);
+        }
+        next = self.next().context("Expected identifier .")?;
         match next {
             Token::Identifier(ident, _, _, _) => {
-                let equals = self.iter.peek();
+                self.declare(&ident, false);
+                let annotation = self.type_annotation()?;
+                let equals = self.peek();
                 match equals {
                     Some(Token::Equal(_, _, _)) => {
-                        self.iter.next().context("Expected equalks.")?;
-                        let dcl = Statement::Declaration(ident, Box::new(self.expression()?));
-                        let semi = self.iter.next().context("Expected ; after expression.")?;
+                        self.next().context("Expected equalks.")?;
+                        let dcl = Statement::Declaration(
+                            ident,
+                            annotation,
+                            Box::new(self.expression()?),
+                            line,
+                        );
+                        let semi = self.next().context("Expected ; after expression.")?;
                         Ok(dcl)
                     }
                     _ => {
-                        let semi = self.iter.next().context("Expected ; after expression.")?;
-                        let dcl = Statement::Declaration(ident, Box::new(Expression::Nil));
+                        let semi = self.next().context("Expected ; after expression.")?;
+                        let dcl = Statement::Declaration(
+                            ident,
+                            annotation,
+                            Box::new(Expression::Nil),
+                            line,
+                        );
                         Ok(dcl)
                     }
                 }
@@ -394,17 +870,448 @@ impl<'a> Parser<'a> {
             _ => Err(anyhow!("Expected var declaration.")),
         }
     }
+    fn destructure_decl(&mut self, line: u32) -> Result<Statement> {
+        let pattern = self.binding_pattern()?;
+        self.assert_next(Token::Equal("=".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error: Destructuring declaration must be initialized."
+            ))?;
+        let value = self.expression()?;
+        self.assert_next(Token::Semicolon(";".into(), 0, 0))
+            .context("Expected ';' after declaration.")?;
+        for name in pattern.names() {
+            self.declare(name, false);
+        }
+        Ok(Statement::DestructureDeclaration(
+            pattern,
+            Box::new(value),
+            line,
+        ))
+    }
+    fn destructure_assign(&mut self) -> Result<Statement> {
+        let line = self.peek().map_or(0, Token::line);
+        let pattern = self.binding_pattern()?;
+        self.assert_next(Token::Equal("=".into(), 0, 0))
+            .context(format!("[line {line}] Error: Expected '=' after pattern."))?;
+        if let Some(name) = pattern
+            .names()
+            .into_iter()
+            .find(|name| self.is_constant(name))
+        {
+            return Err(anyhow!(
+                "[line {line}] Error at '{name}': Cannot assign to constant."
+            ));
+        }
+        let value = self.expression()?;
+        self.assert_next(Token::Semicolon(";".into(), 0, 0))
+            .context("Expected ';' after assignment.")?;
+        Ok(Statement::DestructureAssign(pattern, Box::new(value), line))
+    }
+    fn pattern_name(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Identifier(name, _, _, _)) => Ok(name),
+            Some(token) => Err(anyhow!(
+                "[line {}] Error: Expected name in pattern.",
+                token.line()
+            )),
+            None => Err(anyhow!("Expected name in pattern, got EOF.")),
+        }
+    }
+    fn binding_pattern(&mut self) -> Result<BindingPattern> {
+        match self.next() {
+            Some(Token::Identifier(name, _, _, _)) => Ok(BindingPattern::Name(name)),
+            Some(Token::LeftBracket(_, line, _)) => {
+                let mut items = Vec::new();
+                let mut rest = None;
+                while !matches!(self.peek(), Some(Token::RightBracket(_, _, _))) {
+                    if let Some(Token::Ellipsis(_, _, _)) = self.peek() {
+                        self.next();
+                        rest = Some(self.pattern_name()?);
+                        break;
+                    }
+                    items.push(self.binding_pattern()?);
+                    if !matches!(self.peek(), Some(Token::Comma(_, _, _))) {
+                        break;
+                    }
+                    self.next();
+                }
+                self.assert_next(Token::RightBracket("]".into(), 0, 0))
+                    .context(format!(
+                        "[line {line}] Error: Expected ']' after list pattern."
+                    ))?;
+                Ok(BindingPattern::List(items, rest))
+            }
+            Some(Token::LeftBrace(_, line, _)) => {
+                let mut entries = Vec::new();
+                while !matches!(self.peek(), Some(Token::RightBrace(_, _, _))) {
+                    let key = self.pattern_name()?;
+                    let target = match self.peek() {
+                        Some(Token::Colon(_, _, _)) => {
+                            self.next();
+                            self.binding_pattern()?
+                        }
+                        _ => BindingPattern::Name(key.clone()),
+                    };
+                    entries.push((key, target));
+                    if !matches!(self.peek(), Some(Token::Comma(_, _, _))) {
+                        break;
+                    }
+                    self.next();
+                }
+                self.assert_next(Token::RightBrace("}".into(), 0, 0))
+                    .context(format!(
+                        "[line {line}] Error: Expected '}}' after map pattern."
+                    ))?;
+                Ok(BindingPattern::Map(entries))
+            }
+            Some(Token::LeftParen(_, line, _)) => {
+                let mut items = Vec::new();
+                while !matches!(self.peek(), Some(Token::RightParen(_, _, _))) {
+                    items.push(self.binding_pattern()?);
+                    if !matches!(self.peek(), Some(Token::Comma(_, _, _))) {
+                        break;
+                    }
+                    self.next();
+                }
+                self.assert_next(Token::RightParen(")".into(), 0, 0))
+                    .context(format!(
+                        "[line {line}] Error: Expected ')' after tuple pattern."
+                    ))?;
+                Ok(BindingPattern::Tuple(items))
+            }
+            Some(token) => Err(anyhow!(
+                "[line {}] Error: Expected binding pattern.",
+                token.line()
+            )),
+            None => Err(anyhow!("Expected binding pattern, got EOF.")),
+        }
+    }
+    fn const_decl(&mut self) -> Result<Statement> {
+        let line = self.next().context("Expected const keyword.")?.line();
+        let name = match self.next() {
+            Some(Token::Identifier(name, _, _, _)) => name,
+            _ => return Err(anyhow!("[line {line}] Error: Expected constant name.")),
+        };
+        let annotation = self.type_annotation()?;
+        self.assert_next(Token::Equal("=".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error at '{name}': Constant must be initialized."
+            ))?;
+        let value = self.expression()?;
+        self.assert_next(Token::Semicolon(";".into(), 0, 0))
+            .context("Expected ';' after constant declaration.")?;
+        self.declare(&name, true);
+        Ok(Statement::Const(name, annotation, Box::new(value), line))
+    }
+    fn type_annotation(&mut self) -> Result<Option<Type>> {
+        if !matches!(self.peek(), Some(Token::Colon(_, _, _))) {
+            return Ok(None);
+        }
+        self.next();
+        match self.next() {
+            Some(Token::Identifier(name, line, _, _)) => match name.as_str() {
+                "Number" => Ok(Some(Type::Number)),
+                "String" => Ok(Some(Type::String)),
+                "Bool" => Ok(Some(Type::Bool)),
+                "Nil" => Ok(Some(Type::Nil)),
+                "Any" => Ok(Some(Type::Any)),
+                _ => Err(anyhow!("[line {line}] Error: Unknown type '{name}'.")),
+            },
+            Some(Token::Nil(_, _, _)) => Ok(Some(Type::Nil)),
+            _ => Err(anyhow!("Expected type name after ':'.")),
+        }
+    }
     pub fn declaration(&mut self) -> Result<Statement> {
-        let next = self.iter.peek();
+        if self.at_infix_decl() {
+            return self.infix_decl();
+        }
+        match self.keyword(&["const", "import", "struct", "enum"]) {
+            Some("const") => return self.const_decl(),
+            Some("import") => return self.import_decl(),
+            Some("struct") => return self.struct_decl(),
+            Some("enum") => return self.enum_decl(),
+            _ => {}
+        }
+        let next = self.peek();
         match next {
             Some(Token::Var(_, _, _)) => self.var_decl(),
             _ => self.statement(),
         }
     }
+    /// Which of `words` the next token is acting as a keyword, if any. Words
+    /// added on top of Lox are contextual: they stay identifiers unless the
+    /// tokens after them could not follow a plain name.
+    fn keyword(&mut self, words: &[&'static str]) -> Option<&'static str> {
+        let word = match self.peek() {
+            Some(Token::Identifier(name, _, _, _)) => words.iter().find(|word| *word == name)?,
+            _ => return None,
+        };
+        let is_name = |token: Option<&Token>| matches!(token, Some(Token::Identifier(_, _, _, _)));
+        let is_brace = |token: Option<&Token>| matches!(token, Some(Token::LeftBrace(_, _, _)));
+        let is_keyword = match *word {
+            "const" => is_name(self.peek_nth(1)),
+            "import" => matches!(self.peek_nth(1), Some(Token::String(_, _, _, _))),
+            "struct" | "enum" => is_name(self.peek_nth(1)) && is_brace(self.peek_nth(2)),
+            "try" | "finally" => is_brace(self.peek_nth(1)),
+            // `throw (x)` stays a call; anything that could continue an
+            // expression is read that way
+            "throw" => matches!(
+                self.peek_nth(1),
+                Some(
+                    Token::Identifier(_, _, _, _)
+                        | Token::Number(_, _, _, _)
+                        | Token::String(_, _, _, _)
+                        | Token::Bytes(_, _, _, _)
+                        | Token::True(_, _, _)
+                        | Token::False(_, _, _)
+                        | Token::Nil(_, _, _)
+                        | Token::This(_, _, _)
+                        | Token::Bang(_, _, _)
+                )
+            ),
+            "catch" => {
+                matches!(self.peek_nth(1), Some(Token::LeftParen(_, _, _)))
+                    && is_name(self.peek_nth(2))
+                    && matches!(self.peek_nth(3), Some(Token::RightParen(_, _, _)))
+                    && is_brace(self.peek_nth(4))
+            }
+            "match" => self.at_match_arms(),
+            _ => false,
+        };
+        is_keyword.then_some(*word)
+    }
+    /// Whether `match` is followed by a parenthesized subject and then `{`,
+    /// which a call to something named `match` never is.
+    fn at_match_arms(&mut self) -> bool {
+        let mut depth = 0;
+        let mut n = 1;
+        loop {
+            match self.peek_nth(n) {
+                Some(Token::LeftParen(_, _, _)) => depth += 1,
+                Some(Token::RightParen(_, _, _)) if depth == 1 => {
+                    return matches!(self.peek_nth(n + 1), Some(Token::LeftBrace(_, _, _)))
+                }
+                Some(Token::RightParen(_, _, _)) => depth -= 1,
+                Some(_) if depth > 0 => {}
+                _ => return false,
+            }
+            n += 1;
+        }
+    }
+    /// Whether `infix` starts a declaration rather than naming a variable,
+    /// judged by the operator symbol, precedence and associativity after it.
+    fn at_infix_decl(&mut self) -> bool {
+        if !matches!(self.peek(), Some(Token::Identifier(keyword, _, _, _)) if keyword == "infix") {
+            return false;
+        }
+        let is_symbol = |token: &Token| {
+            let lexeme = token.span().map(|(lexeme, _, _)| lexeme);
+            lexeme.is_some_and(is_operator_symbol)
+        };
+        let mut n = 1;
+        while self.peek_nth(n).is_some_and(is_symbol) {
+            n += 1;
+        }
+        n > 1
+            && matches!(self.peek_nth(n), Some(Token::Number(_, _, _, _)))
+            && matches!(
+                self.peek_nth(n + 1),
+                Some(Token::Identifier(keyword, _, _, _))
+                    if ["left", "right", "none"].contains(&keyword.as_str())
+            )
+    }
+    /// `infix <+> 60 left = f;` declares an operator that calls `f` with its
+    /// operands. The value is bound under the operator's symbol, which no
+    /// identifier can spell.
+    fn infix_decl(&mut self) -> Result<Statement> {
+        let line = self.next().context("Expected infix keyword.")?.line();
+        let mut symbol: Vec<String> = Vec::new();
+        let mut end = None;
+        while let Some((lexeme, line, col)) = self.peek().and_then(Token::span) {
+            if !is_operator_symbol(lexeme) || (end.is_some() && end != Some((line, col))) {
+                break;
+            }
+            end = Some((line, col + lexeme.chars().count() as u32));
+            symbol.push(lexeme.to_owned());
+            self.next();
+        }
+        let name = symbol.concat();
+        if symbol.len() < 2 {
+            return Err(anyhow!(
+                "[line {line}] Error at '{name}': Operator must be spelled by more than one token."
+            ));
+        }
+        if self
+            .operators
+            .iter()
+            .any(|(declared, _, _)| *declared == symbol)
+        {
+            return Err(anyhow!(
+                "[line {line}] Error at '{name}': Operator is already declared."
+            ));
+        }
+        let precedence = match self.next() {
+            Some(Token::Number(_, _, _, n)) if n.fract() == 0.0 && (0.0..=100.0).contains(&n) => {
+                n as u8
+            }
+            _ => {
+                return Err(anyhow!(
+                    "[line {line}] Error at '{name}': Expected a precedence from 0 to 100."
+                ))
+            }
+        };
+        let associativity = match self.next() {
+            Some(Token::Identifier(keyword, _, _, _)) if keyword == "left" => Associativity::Left,
+            Some(Token::Identifier(keyword, _, _, _)) if keyword == "right" => Associativity::Right,
+            Some(Token::Identifier(keyword, _, _, _)) if keyword == "none" => Associativity::None,
+            _ => {
+                return Err(anyhow!(
+                    "[line {line}] Error at '{name}': Expected 'left', 'right' or 'none'."
+                ))
+            }
+        };
+        self.assert_next(Token::Equal("=".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error at '{name}': Expected '=' before operator function."
+            ))?;
+        let value = self.expression()?;
+        self.assert_next(Token::Semicolon(";".into(), 0, 0))
+            .context("Expected ';' after operator declaration.")?;
+        self.operators.push((symbol, precedence, associativity));
+        self.operators
+            .sort_by_key(|(symbol, _, _)| Reverse(symbol.len()));
+        self.declare(&name, true);
+        Ok(Statement::Const(name, None, Box::new(value), line))
+    }
+    fn struct_decl(&mut self) -> Result<Statement> {
+        let line = self.next().context("Expected struct keyword.")?.line();
+        let name = match self.next() {
+            Some(Token::Identifier(name, _, _, _)) => name,
+            _ => return Err(anyhow!("[line {line}] Error: Expected struct name.")),
+        };
+        self.assert_next(Token::LeftBrace("{".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error: Expected '{{' before struct fields."
+            ))?;
+        let mut fields: Vec<String> = Vec::new();
+        while !matches!(self.peek(), Some(Token::RightBrace(_, _, _))) {
+            let field = self.pattern_name()?;
+            if fields.contains(&field) {
+                return Err(anyhow!(
+                    "[line {line}] Error at '{field}': Duplicate field in struct '{name}'."
+                ));
+            }
+            fields.push(field);
+            if !matches!(self.peek(), Some(Token::Comma(_, _, _))) {
+                break;
+            }
+            self.next();
+        }
+        self.assert_next(Token::RightBrace("}".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error: Expected '}}' after struct fields."
+            ))?;
+        self.declare(&name, false);
+        Ok(Statement::Struct(name, fields))
+    }
+    /// `enum Shape { Circle(r), Rect(w, h), Empty }` declares the variants
+    /// of `Shape`, each with the field names of its payload.
+    fn enum_decl(&mut self) -> Result<Statement> {
+        let line = self.next().context("Expected enum keyword.")?.line();
+        let name = match self.next() {
+            Some(Token::Identifier(name, _, _, _)) => name,
+            _ => return Err(anyhow!("[line {line}] Error: Expected enum name.")),
+        };
+        self.assert_next(Token::LeftBrace("{".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error: Expected '{{' before enum variants."
+            ))?;
+        let mut variants: Vec<(String, Vec<String>)> = Vec::new();
+        while !matches!(self.peek(), Some(Token::RightBrace(_, _, _))) {
+            let variant = self.pattern_name()?;
+            if variants.iter().any(|(other, _)| *other == variant) {
+                return Err(anyhow!(
+                    "[line {line}] Error at '{variant}': Duplicate variant in enum '{name}'."
+                ));
+            }
+            let fields = match self.peek() {
+                Some(Token::LeftParen(_, _, _)) => self.field_names(line)?,
+                _ => Vec::new(),
+            };
+            variants.push((variant, fields));
+            if !matches!(self.peek(), Some(Token::Comma(_, _, _))) {
+                break;
+            }
+            self.next();
+        }
+        self.assert_next(Token::RightBrace("}".into(), 0, 0))
+            .context(format!(
+                "[line {line}] Error: Expected '}}' after enum variants."
+            ))?;
+        self.declare(&name, false);
+        Ok(Statement::Enum(name, variants))
+    }
+    /// A parenthesized list of at least one distinct name, as in `(w, h)`.
+    fn field_names(&mut self, line: u32) -> Result<Vec<String>> {
+        self.assert_next(Token::LeftParen("(".into(), 0, 0))?;
+        let mut fields: Vec<String> = Vec::new();
+        loop {
+            let field = self.pattern_name()?;
+            if field != "_" && fields.contains(&field) {
+                return Err(anyhow!(
+                    "[line {line}] Error at '{field}': Duplicate field."
+                ));
+            }
+            fields.push(field);
+            if !matches!(self.peek(), Some(Token::Comma(_, _, _))) {
+                break;
+            }
+            self.next();
+        }
+        self.assert_next(Token::RightParen(")".into(), 0, 0))
+            .context(format!("[line {line}] Error: Expected ')' after fields."))?;
+        Ok(fields)
+    }
+    fn import_decl(&mut self) -> Result<Statement> {
+        let import = self.next().context("Expected import keyword.")?;
+        let line = import.line();
+        let path = match self.next() {
+            Some(Token::String(_, _, _, path)) => path,
+            _ => {
+                return Err(anyhow!(
+                    "[line {line}] Error: Expected module path after 'import'."
+                ))
+            }
+        };
+        let name = match self.peek() {
+            Some(Token::Identifier(keyword, _, _, _)) if keyword == "as" => {
+                self.next();
+                match self.next() {
+                    Some(Token::Identifier(name, _, _, _)) => name,
+                    _ => return Err(anyhow!("[line {line}] Error: Expected name after 'as'.")),
+                }
+            }
+            _ => std::path::Path::new(&path)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .filter(|stem| {
+                    stem.chars().all(|c| c.is_alphanumeric() || c == '_')
+                        && !stem.starts_with(|c: char| c.is_ascii_digit())
+                })
+                .map(str::to_owned)
+                .context(format!(
+                    "[line {line}] Error: Cannot name module '{path}', use 'import \"{path}\" as name;'."
+                ))?,
+        };
+        self.assert_next(Token::Semicolon(";".into(), 0, 0))
+            .context("Expected ';' after import.")?;
+        self.declare(&name, false);
+        Ok(Statement::Import(path, name, line))
+    }
 
     fn program(&mut self) -> Result<Statement> {
         let mut statements = Vec::new();
-        while let Some(token) = self.iter.peek() {
+        while let Some(token) = self.peek() {
             statements.push(self.declaration()?);
         }
         Ok(Statement::Program(statements))
@@ -419,10 +1326,18 @@ impl<'a> Parser<'a> {
         Ok(ast)
     }
     fn peek(&mut self) -> Option<&Token> {
-        self.iter.peek()
+        self.peek_nth(0)
+    }
+    /// Looks `n` tokens past the next one without consuming anything.
+    fn peek_nth(&mut self, n: usize) -> Option<&Token> {
+        while self.lookahead.len() <= n {
+            let token = self.iter.next()?;
+            self.lookahead.push_back(token);
+        }
+        self.lookahead.get(n)
     }
     fn next(&mut self) -> Option<Token> {
-        self.iter.next()
+        self.lookahead.pop_front().or_else(|| self.iter.next())
     }
 }
 impl TryFrom<Token> for Operator {
@@ -567,3 +1482,274 @@ fn test_boolean_2() {
     println!("{:?}", p);
     println!("{}", p.pprint());
 }
+
+#[test]
+fn test_match_stmt() {
+    let tokenizer = Tokenizer::new(
+        "match (x) { 1 | 2 => print \"small\"; 3..=9 if x != 5 => print \"mid\"; _ => print \"big\"; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let p = parser.parse_program().unwrap();
+    let Statement::Program(statements) = p else {
+        panic!("expected program");
+    };
+    let Statement::Match(subject, arms, _) = &statements[0] else {
+        panic!("expected match statement");
+    };
+    assert_eq!(**subject, Expression::Identifier("x".into()));
+    assert_eq!(arms.len(), 3);
+    assert_eq!(
+        arms[0].patterns,
+        vec![
+            Pattern::Literal(Expression::Number(1.0)),
+            Pattern::Literal(Expression::Number(2.0))
+        ]
+    );
+    assert_eq!(arms[1].patterns, vec![Pattern::Range(3.0, 9.0, true)]);
+    assert!(arms[1].guard.is_some());
+    assert_eq!(arms[2].patterns, vec![Pattern::Wildcard]);
+    assert!(parser.warnings().is_empty());
+}
+
+#[test]
+fn test_match_wildcard_not_last() {
+    let tokenizer = Tokenizer::new("match (1) {\n _ => print 1;\n 2 => print 2;\n}".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    parser.parse_program().unwrap();
+    assert_eq!(
+        parser.warnings(),
+        ["[line 2] Warning: Wildcard arm is not the last arm; arms after it are unreachable."]
+    );
+}
+
+#[test]
+fn test_const_assignment() {
+    let tokenizer = Tokenizer::new("const a = 1;\n{ var a = 2; a = 3; }\na = 4;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let err = parser.parse_program().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "[line 3] Error at 'a': Cannot assign to constant."
+    );
+
+    let tokenizer = Tokenizer::new("const a;".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    let err = parser.parse_program().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "[line 1] Error at 'a': Constant must be initialized."
+    );
+}
+
+#[test]
+fn test_method_call() {
+    let tokenizer = Tokenizer::new("name.trim().slice(0, 2 + 1)".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let p = parser.parse().unwrap();
+    assert_eq!(
+        p.pprint(),
+        "(call (. (call (. name trim)) slice) 0.0 (+ 2.0 1.0))"
+    );
+}
+
+#[test]
+fn test_optional_chaining() {
+    let tokenizer = Tokenizer::new("a?.b.c() ?? d[0] and e ?? f or g".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let p = parser.parse().unwrap();
+    assert_eq!(
+        p.pprint(),
+        "(|| (?? (?? (call (. (. (? a) b) c)) (&& (index d 0.0) e)) f) g)"
+    );
+}
+
+#[test]
+fn test_pipeline() {
+    let tokenizer = Tokenizer::new("x = a or b |> f(1) |> g".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let p = parser.parse().unwrap();
+    assert_eq!(p.pprint(), "(= x (|> (|> (|| a b) (call f 1.0)) g))");
+}
+
+#[test]
+fn test_range() {
+    let tokenizer = Tokenizer::new("xs[1..n + 1] == (0..=9.5).step(2)".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let p = parser.parse().unwrap();
+    assert_eq!(
+        p.pprint(),
+        "(== (index xs (.. 1.0 (+ n 1.0))) (call (. (group (..= 0.0 9.5)) step) 2.0))"
+    );
+}
+
+#[test]
+fn test_tuple() {
+    let tokenizer = Tokenizer::new("(a, (b,), c.0.1) == (1)".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let p = parser.parse().unwrap();
+    assert_eq!(
+        p.pprint(),
+        "(== (tuple a (tuple b) (. (. c 0) 1)) (group 1.0))"
+    );
+}
+
+#[test]
+fn test_struct_decl() {
+    let tokenizer = Tokenizer::new("struct Point { x, y } p.with(x: 3, y: -1);".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let Statement::Program(statements) = parser.parse_program().unwrap() else {
+        panic!("expected program");
+    };
+    let Statement::Struct(name, fields) = &statements[0] else {
+        panic!("expected struct declaration");
+    };
+    assert_eq!(name, "Point");
+    assert_eq!(fields, &["x", "y"]);
+    let Statement::ExprStmt(call, _) = &statements[1] else {
+        panic!("expected expression statement");
+    };
+    assert_eq!(call.pprint(), "(call (. p with) (: x 3.0) (: y (- 1.0)))");
+
+    let tokenizer = Tokenizer::new("struct Pair { a, a }".into());
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+    assert_eq!(
+        parser.parse_program().unwrap_err().to_string(),
+        "[line 1] Error at 'a': Duplicate field in struct 'Pair'."
+    );
+}
+
+#[test]
+fn test_infix_operators() {
+    let tokenizer = Tokenizer::new(
+        "infix <+> 60 left = Pair;
+infix ** 95 right = Pair;
+1 + 2 <+> 3 <+> 4 == a < -b;
+2 ** 3 ** 4;
+infix = 1 <+> 2;"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let Statement::Program(statements) = parser.parse_program().unwrap() else {
+        panic!("expected program");
+    };
+    let Statement::Const(name, _, _, _) = &statements[0] else {
+        panic!("expected operator declaration");
+    };
+    assert_eq!(name, "<+>");
+    let expressions: Vec<String> = statements[2..]
+        .iter()
+        .map(|statement| match statement {
+            Statement::ExprStmt(expr, _) => expr.pprint(),
+            _ => panic!("expected expression statement"),
+        })
+        .collect();
+    assert_eq!(
+        expressions,
+        [
+            "(== (call <+> (call <+> (+ 1.0 2.0) 3.0) 4.0) (< a (- b)))",
+            "(call ** 2.0 (call ** 3.0 4.0))",
+            "(= infix (call <+> 1.0 2.0))",
+        ]
+    );
+
+    for (source, error) in [
+        (
+            "infix + 60 left = f;",
+            "[line 1] Error at '+': Operator must be spelled by more than one token.",
+        ),
+        (
+            "infix <+> 101 left = f;",
+            "[line 1] Error at '<+>': Expected a precedence from 0 to 100.",
+        ),
+        (
+            "infix <+> 60 left = f; infix <+> 50 right = g;",
+            "[line 1] Error at '<+>': Operator is already declared.",
+        ),
+    ] {
+        let tokenizer = Tokenizer::new(source.into());
+        let mut iter = tokenizer.iter().peekable();
+        let mut parser = Parser::new(&mut iter);
+        assert_eq!(parser.parse_program().unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn test_enum_decl() {
+    let tokenizer = Tokenizer::new(
+        "enum Shape { Circle(r), Rect(w, h), Empty }
+match (s) { Shape.Rect(w, _) | Shape.Circle(w) => print w; Shape.Empty => print 0; }"
+            .into(),
+    );
+    let mut iter = tokenizer.iter().peekable();
+    let mut parser = Parser::new(&mut iter);
+
+    let Statement::Program(statements) = parser.parse_program().unwrap() else {
+        panic!("expected program");
+    };
+    let Statement::Enum(name, variants) = &statements[0] else {
+        panic!("expected enum declaration");
+    };
+    assert_eq!(name, "Shape");
+    assert_eq!(
+        variants,
+        &[
+            ("Circle".to_string(), vec!["r".to_string()]),
+            ("Rect".to_string(), vec!["w".to_string(), "h".to_string()]),
+            ("Empty".to_string(), vec![]),
+        ]
+    );
+    let Statement::Match(_, arms, _) = &statements[1] else {
+        panic!("expected match");
+    };
+    assert_eq!(
+        arms[0].patterns,
+        vec![
+            Pattern::Variant("Shape".into(), "Rect".into(), vec!["w".into(), "_".into()]),
+            Pattern::Variant("Shape".into(), "Circle".into(), vec!["w".into()]),
+        ]
+    );
+    assert_eq!(arms[0].names(), ["w"]);
+    assert_eq!(
+        arms[1].patterns,
+        vec![Pattern::Variant("Shape".into(), "Empty".into(), vec![])]
+    );
+
+    for (source, error) in [
+        (
+            "enum Shape { Empty, Empty }",
+            "[line 1] Error at 'Empty': Duplicate variant in enum 'Shape'.",
+        ),
+        (
+            "enum Shape { Rect(w, w) }",
+            "[line 1] Error at 'w': Duplicate field.",
+        ),
+    ] {
+        let tokenizer = Tokenizer::new(source.into());
+        let mut iter = tokenizer.iter().peekable();
+        let mut parser = Parser::new(&mut iter);
+        assert_eq!(parser.parse_program().unwrap_err().to_string(), error);
+    }
+}
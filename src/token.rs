@@ -22,10 +22,13 @@ pub enum Token {
     Greater(String, u32, u32),
     GreaterEqual(String, u32, u32),
     Colon(String, u32, u32),
+    Pipe(String, u32, u32),
+    FatArrow(String, u32, u32),
     Error(String),
     Number(String, u32, u32, f64),
     Identifier(String, u32, u32, String),
     String(String, u32, u32, String),
+    Bytes(String, u32, u32, Vec<u8>),
     And(String, u32, u32),
     Class(String, u32, u32),
     Else(String, u32, u32),
@@ -42,9 +45,191 @@ pub enum Token {
     True(String, u32, u32),
     Var(String, u32, u32),
     While(String, u32, u32),
+    LeftBracket(String, u32, u32),
+    RightBracket(String, u32, u32),
+    Ellipsis(String, u32, u32),
+    QuestionDot(String, u32, u32),
+    QuestionQuestion(String, u32, u32),
+    PipeGreater(String, u32, u32),
+    DotDot(String, u32, u32),
+    DotDotEqual(String, u32, u32),
+    Dollar(String, u32, u32),
     EndOfFile,
 }
 
+impl Token {
+    pub fn line(&self) -> u32 {
+        match self {
+            Token::LeftParen(_, line, _)
+            | Token::RightParen(_, line, _)
+            | Token::LeftBrace(_, line, _)
+            | Token::RightBrace(_, line, _)
+            | Token::Star(_, line, _)
+            | Token::Dot(_, line, _)
+            | Token::Comma(_, line, _)
+            | Token::Plus(_, line, _)
+            | Token::Minus(_, line, _)
+            | Token::Semicolon(_, line, _)
+            | Token::Slash(_, line, _)
+            | Token::Equal(_, line, _)
+            | Token::EqualEqual(_, line, _)
+            | Token::Bang(_, line, _)
+            | Token::BangEqual(_, line, _)
+            | Token::Less(_, line, _)
+            | Token::LessEqual(_, line, _)
+            | Token::Greater(_, line, _)
+            | Token::GreaterEqual(_, line, _)
+            | Token::Colon(_, line, _)
+            | Token::Pipe(_, line, _)
+            | Token::FatArrow(_, line, _)
+            | Token::And(_, line, _)
+            | Token::Class(_, line, _)
+            | Token::Else(_, line, _)
+            | Token::False(_, line, _)
+            | Token::For(_, line, _)
+            | Token::Fun(_, line, _)
+            | Token::If(_, line, _)
+            | Token::Nil(_, line, _)
+            | Token::Or(_, line, _)
+            | Token::Print(_, line, _)
+            | Token::Return(_, line, _)
+            | Token::Super(_, line, _)
+            | Token::This(_, line, _)
+            | Token::True(_, line, _)
+            | Token::Var(_, line, _)
+            | Token::While(_, line, _)
+            | Token::LeftBracket(_, line, _)
+            | Token::RightBracket(_, line, _)
+            | Token::Ellipsis(_, line, _)
+            | Token::QuestionDot(_, line, _)
+            | Token::QuestionQuestion(_, line, _)
+            | Token::PipeGreater(_, line, _)
+            | Token::DotDot(_, line, _)
+            | Token::DotDotEqual(_, line, _)
+            | Token::Dollar(_, line, _) => *line,
+            Token::Number(_, line, _, _)
+            | Token::Identifier(_, line, _, _)
+            | Token::String(_, line, _, _)
+            | Token::Bytes(_, line, _, _) => *line,
+            Token::Error(_) | Token::EndOfFile => 0,
+        }
+    }
+    /// Moves the token to another line, keeping its column.
+    pub fn set_line(&mut self, new_line: u32) {
+        match self {
+            Token::LeftParen(_, line, _)
+            | Token::RightParen(_, line, _)
+            | Token::LeftBrace(_, line, _)
+            | Token::RightBrace(_, line, _)
+            | Token::Star(_, line, _)
+            | Token::Dot(_, line, _)
+            | Token::Comma(_, line, _)
+            | Token::Plus(_, line, _)
+            | Token::Minus(_, line, _)
+            | Token::Semicolon(_, line, _)
+            | Token::Slash(_, line, _)
+            | Token::Equal(_, line, _)
+            | Token::EqualEqual(_, line, _)
+            | Token::Bang(_, line, _)
+            | Token::BangEqual(_, line, _)
+            | Token::Less(_, line, _)
+            | Token::LessEqual(_, line, _)
+            | Token::Greater(_, line, _)
+            | Token::GreaterEqual(_, line, _)
+            | Token::Colon(_, line, _)
+            | Token::Pipe(_, line, _)
+            | Token::FatArrow(_, line, _)
+            | Token::And(_, line, _)
+            | Token::Class(_, line, _)
+            | Token::Else(_, line, _)
+            | Token::False(_, line, _)
+            | Token::For(_, line, _)
+            | Token::Fun(_, line, _)
+            | Token::If(_, line, _)
+            | Token::Nil(_, line, _)
+            | Token::Or(_, line, _)
+            | Token::Print(_, line, _)
+            | Token::Return(_, line, _)
+            | Token::Super(_, line, _)
+            | Token::This(_, line, _)
+            | Token::True(_, line, _)
+            | Token::Var(_, line, _)
+            | Token::While(_, line, _)
+            | Token::LeftBracket(_, line, _)
+            | Token::RightBracket(_, line, _)
+            | Token::Ellipsis(_, line, _)
+            | Token::QuestionDot(_, line, _)
+            | Token::QuestionQuestion(_, line, _)
+            | Token::PipeGreater(_, line, _)
+            | Token::DotDot(_, line, _)
+            | Token::DotDotEqual(_, line, _)
+            | Token::Dollar(_, line, _) => *line = new_line,
+            Token::Number(_, line, _, _)
+            | Token::Identifier(_, line, _, _)
+            | Token::String(_, line, _, _)
+            | Token::Bytes(_, line, _, _) => *line = new_line,
+            Token::Error(_) | Token::EndOfFile => {}
+        }
+    }
+    /// The token's source text with its line and 1-based column.
+    pub fn span(&self) -> Option<(&str, u32, u32)> {
+        match self {
+            Token::LeftParen(lexeme, line, col)
+            | Token::RightParen(lexeme, line, col)
+            | Token::LeftBrace(lexeme, line, col)
+            | Token::RightBrace(lexeme, line, col)
+            | Token::Star(lexeme, line, col)
+            | Token::Dot(lexeme, line, col)
+            | Token::Comma(lexeme, line, col)
+            | Token::Plus(lexeme, line, col)
+            | Token::Minus(lexeme, line, col)
+            | Token::Semicolon(lexeme, line, col)
+            | Token::Slash(lexeme, line, col)
+            | Token::Equal(lexeme, line, col)
+            | Token::EqualEqual(lexeme, line, col)
+            | Token::Bang(lexeme, line, col)
+            | Token::BangEqual(lexeme, line, col)
+            | Token::Less(lexeme, line, col)
+            | Token::LessEqual(lexeme, line, col)
+            | Token::Greater(lexeme, line, col)
+            | Token::GreaterEqual(lexeme, line, col)
+            | Token::Colon(lexeme, line, col)
+            | Token::Pipe(lexeme, line, col)
+            | Token::FatArrow(lexeme, line, col)
+            | Token::And(lexeme, line, col)
+            | Token::Class(lexeme, line, col)
+            | Token::Else(lexeme, line, col)
+            | Token::False(lexeme, line, col)
+            | Token::For(lexeme, line, col)
+            | Token::Fun(lexeme, line, col)
+            | Token::If(lexeme, line, col)
+            | Token::Nil(lexeme, line, col)
+            | Token::Or(lexeme, line, col)
+            | Token::Print(lexeme, line, col)
+            | Token::Return(lexeme, line, col)
+            | Token::Super(lexeme, line, col)
+            | Token::This(lexeme, line, col)
+            | Token::True(lexeme, line, col)
+            | Token::Var(lexeme, line, col)
+            | Token::While(lexeme, line, col)
+            | Token::LeftBracket(lexeme, line, col)
+            | Token::RightBracket(lexeme, line, col)
+            | Token::Ellipsis(lexeme, line, col)
+            | Token::QuestionDot(lexeme, line, col)
+            | Token::QuestionQuestion(lexeme, line, col)
+            | Token::PipeGreater(lexeme, line, col)
+            | Token::DotDot(lexeme, line, col)
+            | Token::DotDotEqual(lexeme, line, col)
+            | Token::Dollar(lexeme, line, col) => Some((lexeme, *line, *col)),
+            Token::Number(lexeme, line, col, _)
+            | Token::Identifier(lexeme, line, col, _)
+            | Token::String(lexeme, line, col, _)
+            | Token::Bytes(lexeme, line, col, _) => Some((lexeme, *line, *col)),
+            Token::Error(_) | Token::EndOfFile => None,
+        }
+    }
+}
+
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -88,6 +273,8 @@ impl fmt::Display for Token {
                 write!(f, "{} {} {}", "EQUAL_EQUAL", lexeme, "null")
             }
             Token::Colon(lexeme, line, col) => write!(f, "{} {} {}", "COLON", lexeme, "null"),
+            Token::Pipe(lexeme, _, _) => write!(f, "PIPE {lexeme} null"),
+            Token::FatArrow(lexeme, _, _) => write!(f, "FAT_ARROW {lexeme} null"),
             Token::Bang(lexeme, line, col) => write!(f, "{} {} {}", "BANG", lexeme, "null"),
             Token::BangEqual(lexeme, line, col) => {
                 write!(f, "{} {} {}", "BANG_EQUAL", lexeme, "null")
@@ -103,6 +290,9 @@ impl fmt::Display for Token {
             Token::String(lexeme, line, col, ident) => {
                 write!(f, "{} {} {}", "STRING", lexeme, ident)
             }
+            Token::Bytes(lexeme, _, _, bytes) => {
+                write!(f, "BYTES {lexeme} {}", bytes.escape_ascii())
+            }
             Token::And(lexeme, line, col) => write!(f, "{} {} {}", "AND", lexeme, "null"),
             Token::Class(lexeme, line, col) => write!(f, "{} {} {}", "CLASS", lexeme, "null"),
             Token::Else(lexeme, line, col) => write!(f, "{} {} {}", "ELSE", lexeme, "null"),
@@ -119,6 +309,15 @@ impl fmt::Display for Token {
             Token::True(lexeme, line, col) => write!(f, "{} {} {}", "TRUE", lexeme, "null"),
             Token::Var(lexeme, line, col) => write!(f, "{} {} {}", "VAR", lexeme, "null"),
             Token::While(lexeme, line, col) => write!(f, "{} {} {}", "WHILE", lexeme, "null"),
+            Token::LeftBracket(lexeme, _, _) => write!(f, "LEFT_BRACKET {lexeme} null"),
+            Token::RightBracket(lexeme, _, _) => write!(f, "RIGHT_BRACKET {lexeme} null"),
+            Token::Ellipsis(lexeme, _, _) => write!(f, "ELLIPSIS {lexeme} null"),
+            Token::QuestionDot(lexeme, _, _) => write!(f, "QUESTION_DOT {lexeme} null"),
+            Token::QuestionQuestion(lexeme, _, _) => write!(f, "QUESTION_QUESTION {lexeme} null"),
+            Token::PipeGreater(lexeme, _, _) => write!(f, "PIPE_GREATER {lexeme} null"),
+            Token::DotDot(lexeme, _, _) => write!(f, "DOT_DOT {lexeme} null"),
+            Token::DotDotEqual(lexeme, _, _) => write!(f, "DOT_DOT_EQUAL {lexeme} null"),
+            Token::Dollar(lexeme, _, _) => write!(f, "DOLLAR {lexeme} null"),
         }
     }
 }
@@ -2,8 +2,10 @@ use std::io::{self, Write};
 use std::iter;
 use std::iter::Peekable;
 use std::str::Chars;
+use std::vec;
 
 use crate::token::Token;
+use crate::unicode::{is_xid_continue, is_xid_start};
 
 use anyhow::Ok;
 use anyhow::Result;
@@ -15,7 +17,10 @@ pub struct Tokenizer {
 pub struct TokenIter<'a> {
     iter: Peekable<Chars<'a>>,
     line: u32,
+    // Characters consumed on the current line before the token being scanned
     col: u32,
+    // Tokens handed out before any scanning, such as a macro-expanded program
+    replay: vec::IntoIter<Token>,
 }
 
 impl Tokenizer {
@@ -24,6 +29,94 @@ impl Tokenizer {
             iter: self.file_content.chars().peekable(),
             line: 1,
             col: 0,
+            replay: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl TokenIter<'_> {
+    /// Hands out already scanned tokens, so the parser can read a token
+    /// stream that was rewritten after scanning.
+    pub fn replay(tokens: Vec<Token>) -> Self {
+        TokenIter {
+            iter: "".chars().peekable(),
+            line: 1,
+            col: 0,
+            replay: tokens.into_iter(),
+        }
+    }
+}
+
+impl TokenIter<'_> {
+    /// Builds a token starting at the current column and moves past its lexeme.
+    /// Columns are 1-based and count characters, not bytes.
+    fn token(&mut self, kind: fn(String, u32, u32) -> Token, lexeme: String) -> Option<Token> {
+        let col = self.col + 1;
+        self.col += lexeme.chars().count() as u32;
+        Some(kind(lexeme, self.line, col))
+    }
+    /// Scans a `b"..."` literal after its `b`. Besides raw characters, which
+    /// are stored UTF-8 encoded, it accepts the escapes `\xNN`, `\n`, `\r`,
+    /// `\t`, `\0`, `\\` and `\"`.
+    fn bytes_literal(&mut self) -> Option<Token> {
+        let line = self.line;
+        let mut lexeme = String::from("b");
+        lexeme.extend(self.iter.next());
+        let mut bytes = Vec::new();
+        let mut invalid = None;
+        loop {
+            let Some(c) = self.iter.next() else {
+                return Some(Token::Error(format!(
+                    "[line {}] Error: Unterminated bytes literal.",
+                    line
+                )));
+            };
+            lexeme.push(c);
+            match c {
+                '"' => break,
+                '\\' => {
+                    let escape = self.iter.next();
+                    lexeme.extend(escape);
+                    let byte = match escape {
+                        Some('x') => {
+                            let hex: String =
+                                iter::from_fn(|| self.iter.next_if(char::is_ascii_hexdigit))
+                                    .take(2)
+                                    .collect();
+                            lexeme.push_str(&hex);
+                            u8::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 2)
+                        }
+                        Some('n') => Some(b'\n'),
+                        Some('r') => Some(b'\r'),
+                        Some('t') => Some(b'\t'),
+                        Some('0') => Some(0),
+                        Some('\\') => Some(b'\\'),
+                        Some('"') => Some(b'"'),
+                        _ => None,
+                    };
+                    match byte {
+                        Some(byte) => bytes.push(byte),
+                        None => invalid = invalid.or(Some(lexeme.clone())),
+                    }
+                }
+                c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        let col = self.col + 1;
+        match lexeme.rsplit_once('\n') {
+            Some((before, last)) => {
+                self.line += before.matches('\n').count() as u32 + 1;
+                self.col = last.chars().count() as u32;
+            }
+            None => self.col += lexeme.chars().count() as u32,
+        }
+        match invalid {
+            Some(scanned) => Some(Token::Error(format!(
+                "[line {}] Error: Invalid escape '{}' in bytes literal.",
+                line,
+                &scanned[scanned.rfind('\\').unwrap()..]
+            ))),
+            None => Some(Token::Bytes(lexeme, line, col, bytes)),
         }
     }
 }
@@ -32,19 +125,36 @@ impl<'a> Iterator for TokenIter<'a> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.replay.next() {
+            return Some(token);
+        }
         loop {
             if let Some(ch) = self.iter.next() {
                 match ch {
-                    '(' => return Some(Token::LeftParen(ch.to_string(), self.line, self.col)),
-                    ')' => return Some(Token::RightParen(ch.to_string(), self.line, self.col)),
-                    '{' => return Some(Token::LeftBrace(ch.to_string(), self.line, self.col)),
-                    '}' => return Some(Token::RightBrace(ch.to_string(), self.line, self.col)),
-                    '*' => return Some(Token::Star(ch.to_string(), self.line, self.col)),
-                    '.' => return Some(Token::Dot(ch.to_string(), self.line, self.col)),
-                    ',' => return Some(Token::Comma(ch.to_string(), self.line, self.col)),
-                    '+' => return Some(Token::Plus(ch.to_string(), self.line, self.col)),
-                    '-' => return Some(Token::Minus(ch.to_string(), self.line, self.col)),
-                    ';' => return Some(Token::Semicolon(ch.to_string(), self.line, self.col)),
+                    '(' => return self.token(Token::LeftParen, ch.to_string()),
+                    ')' => return self.token(Token::RightParen, ch.to_string()),
+                    '{' => return self.token(Token::LeftBrace, ch.to_string()),
+                    '}' => return self.token(Token::RightBrace, ch.to_string()),
+                    '[' => return self.token(Token::LeftBracket, ch.to_string()),
+                    ']' => return self.token(Token::RightBracket, ch.to_string()),
+                    '*' => return self.token(Token::Star, ch.to_string()),
+                    '$' => return self.token(Token::Dollar, ch.to_string()),
+                    '.' => {
+                        if self.iter.next_if_eq(&'.').is_none() {
+                            return self.token(Token::Dot, ch.to_string());
+                        }
+                        if self.iter.next_if_eq(&'.').is_some() {
+                            return self.token(Token::Ellipsis, "...".into());
+                        }
+                        if self.iter.next_if_eq(&'=').is_some() {
+                            return self.token(Token::DotDotEqual, "..=".into());
+                        }
+                        return self.token(Token::DotDot, "..".into());
+                    }
+                    ',' => return self.token(Token::Comma, ch.to_string()),
+                    '+' => return self.token(Token::Plus, ch.to_string()),
+                    '-' => return self.token(Token::Minus, ch.to_string()),
+                    ';' => return self.token(Token::Semicolon, ch.to_string()),
                     '\"' => {
                         let identifier: String = iter::once(ch)
                             .chain(std::iter::from_fn(|| {
@@ -61,8 +171,15 @@ impl<'a> Iterator for TokenIter<'a> {
                             self.iter.next();
                             let noquote = (&identifier[1..]).to_owned();
                             let s = identifier + "\"";
-
-                            return Some(Token::String(s, self.line, self.col, noquote));
+                            let token = Token::String(s.clone(), self.line, self.col + 1, noquote);
+                            match s.rsplit_once('\n') {
+                                Some((before, last)) => {
+                                    self.line += before.matches('\n').count() as u32 + 1;
+                                    self.col = last.chars().count() as u32;
+                                }
+                                None => self.col += s.chars().count() as u32,
+                            }
+                            return Some(token);
                         } else {
                             return Some(Token::Error(format!(
                                 "[line {}] Error: Unterminated string.",
@@ -80,12 +197,13 @@ impl<'a> Iterator for TokenIter<'a> {
                             while let Some(c) = self.iter.next() {
                                 if c == '\n' {
                                     self.line += 1;
+                                    self.col = 0;
                                     break;
                                 }
                             }
                             continue;
                         } else {
-                            return Some(Token::Slash(ch.to_string(), self.line, self.col));
+                            return self.token(Token::Slash, ch.to_string());
                         }
                     }
                     '>' => {
@@ -93,9 +211,9 @@ impl<'a> Iterator for TokenIter<'a> {
                             .chain(self.iter.by_ref().next_if_eq(&'='))
                             .collect::<String>();
                         if geq == ">=" {
-                            return Some(Token::GreaterEqual(geq.to_string(), self.line, self.col));
+                            return self.token(Token::GreaterEqual, geq.to_string());
                         } else {
-                            return Some(Token::Greater(ch.to_string(), self.line, self.col));
+                            return self.token(Token::Greater, ch.to_string());
                         }
                     }
                     '<' => {
@@ -103,9 +221,9 @@ impl<'a> Iterator for TokenIter<'a> {
                             .chain(self.iter.by_ref().next_if_eq(&'='))
                             .collect::<String>();
                         if leq == "<=" {
-                            return Some(Token::LessEqual(leq.to_string(), self.line, self.col));
+                            return self.token(Token::LessEqual, leq.to_string());
                         } else {
-                            return Some(Token::Less(ch.to_string(), self.line, self.col));
+                            return self.token(Token::Less, ch.to_string());
                         }
                     }
                     '!' => {
@@ -113,106 +231,90 @@ impl<'a> Iterator for TokenIter<'a> {
                             .chain(self.iter.by_ref().next_if_eq(&'='))
                             .collect::<String>();
                         if beq == "!=" {
-                            return Some(Token::BangEqual(beq.to_string(), self.line, self.col));
+                            return self.token(Token::BangEqual, beq.to_string());
                         } else {
-                            return Some(Token::Bang(ch.to_string(), self.line, self.col));
+                            return self.token(Token::Bang, ch.to_string());
                         }
                     }
                     '=' => {
                         let eq: String = iter::once(ch)
-                            .chain(self.iter.by_ref().next_if_eq(&'='))
+                            .chain(self.iter.by_ref().next_if(|c| *c == '=' || *c == '>'))
                             .collect::<String>();
                         if eq == "==" {
-                            return Some(Token::EqualEqual(eq.to_string(), self.line, self.col));
+                            return self.token(Token::EqualEqual, eq.to_string());
+                        } else if eq == "=>" {
+                            return self.token(Token::FatArrow, eq.to_string());
                         } else {
-                            return Some(Token::Equal(ch.to_string(), self.line, self.col));
+                            return self.token(Token::Equal, ch.to_string());
                         }
                     }
-                    ':' => return Some(Token::Colon(ch.to_string(), self.line, self.col)),
+                    ':' => return self.token(Token::Colon, ch.to_string()),
+                    '|' if self.iter.next_if_eq(&'>').is_some() => {
+                        return self.token(Token::PipeGreater, "|>".into())
+                    }
+                    '|' => return self.token(Token::Pipe, ch.to_string()),
+                    '?' if self.iter.next_if_eq(&'.').is_some() => {
+                        return self.token(Token::QuestionDot, "?.".into())
+                    }
+                    '?' if self.iter.next_if_eq(&'?').is_some() => {
+                        return self.token(Token::QuestionQuestion, "??".into())
+                    }
                     '0'..='9' => {
-                        let n = iter::once(ch) // Start with the initial digit
+                        let mut n = iter::once(ch) // Start with the initial digit
                             .chain(std::iter::from_fn(|| {
-                                self.iter
-                                    .by_ref()
-                                    .next_if(|c| (c.is_ascii_digit() || *c == '.'))
+                                self.iter.by_ref().next_if(|c| c.is_ascii_digit())
                             })) // Chain subsequent digits
                             .collect::<String>(); // Collect them into a String
-                                                  // Parse the string into an i64
-                        self.col += n.to_string().len() as u32;
+
+                        // Only take the '.' when a digit follows, so `1..5` stays two numbers
+                        let mut lookahead = self.iter.clone();
+                        if lookahead.next() == Some('.')
+                            && lookahead.peek().is_some_and(|c| c.is_ascii_digit())
+                        {
+                            n.extend(self.iter.next());
+                            n.extend(std::iter::from_fn(|| {
+                                self.iter.by_ref().next_if(|c| c.is_ascii_digit())
+                            }));
+                        }
+                        let col = self.col + 1;
+                        self.col += n.len() as u32;
 
                         // Push the number token
-                        return Some(Token::Number(
-                            n.clone(),
-                            self.line,
-                            self.col,
-                            n.parse().unwrap(),
-                        ));
+                        return Some(Token::Number(n.clone(), self.line, col, n.parse().unwrap()));
                     }
-                    '_' | 'A'..='z' => {
+                    'b' if self.iter.peek() == Some(&'\"') => return self.bytes_literal(),
+                    c if c == '_' || is_xid_start(c) => {
                         let identifier: String = iter::once(ch)
                             .chain(std::iter::from_fn(|| {
-                                self.iter
-                                    .by_ref()
-                                    .next_if(|c| c.is_alphanumeric() || *c == '_')
+                                self.iter.by_ref().next_if(|c| is_xid_continue(*c))
                             }))
                             .collect::<String>()
                             .parse()
                             .unwrap();
-                        self.col += identifier.len() as u32;
                         match identifier.as_str() {
-                            "and" => {
-                                return Some(Token::And(identifier.clone(), self.line, self.col))
-                            }
-                            "class" => {
-                                return Some(Token::Class(identifier.clone(), self.line, self.col))
-                            }
-                            "else" => {
-                                return Some(Token::Else(identifier.clone(), self.line, self.col))
-                            }
-                            "false" => {
-                                return Some(Token::False(identifier.clone(), self.line, self.col))
-                            }
-                            "for" => {
-                                return Some(Token::For(identifier.clone(), self.line, self.col))
-                            }
-                            "fun" => {
-                                return Some(Token::Fun(identifier.clone(), self.line, self.col))
-                            }
-                            "if" => {
-                                return Some(Token::If(identifier.clone(), self.line, self.col))
-                            }
-                            "nil" => {
-                                return Some(Token::Nil(identifier.clone(), self.line, self.col))
-                            }
-                            "or" => {
-                                return Some(Token::Or(identifier.clone(), self.line, self.col))
-                            }
-                            "print" => {
-                                return Some(Token::Print(identifier.clone(), self.line, self.col))
-                            }
-                            "return" => {
-                                return Some(Token::Return(identifier.clone(), self.line, self.col))
-                            }
-                            "super" => {
-                                return Some(Token::Super(identifier.clone(), self.line, self.col))
-                            }
-                            "this" => {
-                                return Some(Token::This(identifier.clone(), self.line, self.col))
-                            }
-                            "true" => {
-                                return Some(Token::True(identifier.clone(), self.line, self.col))
-                            }
-                            "var" => {
-                                return Some(Token::Var(identifier.clone(), self.line, self.col))
-                            }
-                            "while" => {
-                                return Some(Token::While(identifier.clone(), self.line, self.col))
-                            }
+                            "and" => return self.token(Token::And, identifier.clone()),
+                            "class" => return self.token(Token::Class, identifier.clone()),
+                            "else" => return self.token(Token::Else, identifier.clone()),
+                            "false" => return self.token(Token::False, identifier.clone()),
+                            "for" => return self.token(Token::For, identifier.clone()),
+                            "fun" => return self.token(Token::Fun, identifier.clone()),
+                            "if" => return self.token(Token::If, identifier.clone()),
+                            "nil" => return self.token(Token::Nil, identifier.clone()),
+                            "or" => return self.token(Token::Or, identifier.clone()),
+                            "print" => return self.token(Token::Print, identifier.clone()),
+                            "return" => return self.token(Token::Return, identifier.clone()),
+                            "super" => return self.token(Token::Super, identifier.clone()),
+                            "this" => return self.token(Token::This, identifier.clone()),
+                            "true" => return self.token(Token::True, identifier.clone()),
+                            "var" => return self.token(Token::Var, identifier.clone()),
+                            "while" => return self.token(Token::While, identifier.clone()),
                             _ => {
+                                let col = self.col + 1;
+                                self.col += identifier.chars().count() as u32;
                                 return Some(Token::Identifier(
                                     identifier.clone(),
                                     self.line,
-                                    self.col,
+                                    col,
                                     identifier,
                                 ));
                             }
@@ -235,6 +337,7 @@ impl<'a> Iterator for TokenIter<'a> {
                         continue;
                     }
                     _ => {
+                        self.col += 1;
                         return Some(Token::Error(format!(
                             "[line {}] Error: Unexpected character: {}",
                             self.line, ch
@@ -441,3 +544,50 @@ impl Tokenizer {
     //     Ok(tokens)
     // }
 }
+
+#[test]
+fn test_unicode_identifiers() {
+    let tokenizer = Tokenizer::new("var café = \"ünï\" + λ_1;\n  über;".into());
+    let tokens: Vec<Token> = tokenizer.iter().collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Var("var".into(), 1, 1),
+            Token::Identifier("café".into(), 1, 5, "café".into()),
+            Token::Equal("=".into(), 1, 10),
+            Token::String("\"ünï\"".into(), 1, 12, "ünï".into()),
+            Token::Plus("+".into(), 1, 18),
+            Token::Identifier("λ_1".into(), 1, 20, "λ_1".into()),
+            Token::Semicolon(";".into(), 1, 23),
+            Token::Identifier("über".into(), 2, 3, "über".into()),
+            Token::Semicolon(";".into(), 2, 7),
+        ]
+    );
+
+    let tokenizer = Tokenizer::new("a^b".into());
+    let tokens: Vec<String> = tokenizer.iter().map(|token| token.to_string()).collect();
+    assert_eq!(
+        tokens,
+        [
+            "IDENTIFIER a null",
+            "[line 1] Error: Unexpected character: ^",
+            "IDENTIFIER b null"
+        ]
+    );
+}
+
+#[test]
+fn test_bytes_literals() {
+    let tokenizer = Tokenizer::new("b\"é\\x00\\\"\" b;\nb\"\\q\" b\"".into());
+    let tokens: Vec<Token> = tokenizer.iter().collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Bytes("b\"é\\x00\\\"\"".into(), 1, 1, vec![0xc3, 0xa9, 0, b'"']),
+            Token::Identifier("b".into(), 1, 12, "b".into()),
+            Token::Semicolon(";".into(), 1, 13),
+            Token::Error("[line 2] Error: Invalid escape '\\q' in bytes literal.".into()),
+            Token::Error("[line 2] Error: Unterminated bytes literal.".into()),
+        ]
+    );
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Writes `source` to a scratch file and runs the interpreter's `command` on
+/// it, removing the file afterwards.
+fn lox(command: &str, name: &str, source: &str) -> Output {
+    let path = env::temp_dir().join(format!("lox-cli-{}-{name}.lox", std::process::id()));
+    fs::write(&path, source).unwrap();
+    let output = interpreter(command, &path);
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+fn interpreter(command: &str, path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_codecrafters-interpreter"))
+        .arg(command)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_run_reports_parse_errors() {
+    let output = lox("run", "const", "const x = 1;\nx = 2;");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).trim_end(),
+        "[line 2] Error at 'x': Cannot assign to constant."
+    );
+}
+
+#[test]
+fn test_check_missing_file() {
+    let path = env::temp_dir().join(format!("lox-cli-{}-missing.lox", std::process::id()));
+    let output = interpreter("check", &path);
+
+    assert_eq!(output.status.code(), Some(66));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with(&format!("Cannot read '{}': ", path.display())),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_expand_missing_file() {
+    let path = env::temp_dir().join(format!("lox-cli-{}-unexpanded.lox", std::process::id()));
+    let output = interpreter("expand", &path);
+
+    assert_eq!(output.status.code(), Some(66));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with(&format!("Cannot read '{}': ", path.display())),
+        "{stderr}"
+    );
+}